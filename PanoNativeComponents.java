@@ -23,9 +23,21 @@ public class PanoNativeComponents {
 
     static native void unmute(String appId);
 
-    static native void notify(String title, String body);
+    static native int notify(String title, String body, String actionsJson, String defaultAction, byte[] imageBytes, String optionsJson, int replacesId);
 
-    static native void setTrayLinux(String tooltip, byte[] pngBytes, boolean invert, String[] menuItemIds, String[] menuItemTexts);
+    static native void closeNotification(int id);
+
+    static native String getNotificationCapabilitiesLinux();
+
+    static native boolean setNotificationRateLimits(String configJson);
+
+    static native void setTrayLinux(String tooltip, byte[] iconBytes, String iconName, String iconColor, String menuJson, boolean menuOnActivate);
+
+    static native void setTrayStatusLinux(String status, String attentionIconName, byte[] attentionIconBytes, String overlayIconName, byte[] overlayIconBytes);
+
+    static native boolean setTrayTooltipLinux(String configJson);
+
+    static native boolean isTrayAvailableLinux();
 
     static native String getMachineId();
 
@@ -33,20 +45,46 @@ public class PanoNativeComponents {
 
     static native boolean sendIpcCommand(String command, String arg);
 
+    static native void setIpcProfileId(String profileId);
+
+    static native String[] listIpcInstances();
+
+    static native void setIpcFilesystemSocketLinux(boolean enabled);
+
     static native boolean isFileLockedWindows(String path);
 
     static native void fileChooser(int requestId, boolean save, String title, String fileName, String[] filters);
 
     static native void onFilePicked(int requestId, String uri);
 
-    static native boolean updateDiscordActivity(String clientId, String name, String state, String details, String largeText, long startTime, long endTime, String artUrl, String detailsUrl, boolean isPlaying, int statusLine, String buttonText, String buttonUrl);
+    // activityJson fields: clientId, activityType ("listening" or "watching"), name, state, stateUrl, details, detailsUrl,
+    // artUrl, largeText, largeUrl, playingSmallImage, playingSmallText, pausedSmallImage, pausedSmallText,
+    // startTime, endTime, statusLine, isPlaying, buttons ([{label, url}], up to 2), partySize ([current, max])
+    static native boolean updateDiscordActivity(String activityJson);
 
     static native boolean clearDiscordActivity(boolean shutdown);
 
+    // {"connected": boolean, "userId": String, "username": String, "clientName": String}
+    static native String getDiscordStatus();
+
+    // serves the now playing overlay on http://127.0.0.1:<port>/, returns false if the port is unavailable
+    static native boolean setNowPlayingServer(boolean enabled, int port);
+
+    // configJson fields: file, template (e.g. "{artist} - {title} [{album}]"), pausedText, stoppedText, fieldsDir, coverFile
+    // placeholders: {title} {artist} {album} {album_artist} {track_number} {duration} {app_id}
+    // pausedText defaults to the template, pass "" to clear the file while paused. stoppedText defaults to ""
+    // pass an empty string to stop writing
+    static native boolean setNowPlayingFileOutput(String configJson);
+
     static native boolean autoStartLinux(boolean add);
 
     static native void openUrl(String url);
 
+    static native void registerUrlSchemeLinux(boolean add);
+
+    // returns false if the link is invalid or there is no running instance to forward it to
+    static native boolean forwardDeepLink(String url);
+
     static {
         System.loadLibrary("pano_native_components");
     }
@@ -70,6 +108,9 @@ public class PanoNativeComponents {
         applyDarkModeWindows(0);
         setEnvironmentVariable("GDK_BACKEND", "x11");
 
+        setIpcProfileId("debug");
+        setIpcFilesystemSocketLinux(true);
+        System.out.println("ipc instances: " + Arrays.toString(listIpcInstances()));
         sendIpcCommand("testCommand", "testArg");
 
         new Thread(new Runnable() {
@@ -98,11 +139,31 @@ public class PanoNativeComponents {
                     e.printStackTrace();
                 }
 
-                PanoNativeComponents.notify("Test Notification", "This is a test notification");
+                setNotificationRateLimits("{\"quietPeriodMs\": 5000, \"categories\": {\"x-pano.scrobbleError\": {\"maxPerWindow\": 3, \"windowMs\": 60000, \"summaryTitle\": \"{count} scrobbles failed\"}}}");
+
+                int notificationId = PanoNativeComponents.notify(
+                        "Test Notification",
+                        "This is a test notification",
+                        "[{\"id\": \"love\", \"label\": \"Love\"}, {\"id\": \"skip\", \"label\": \"Skip\"}]",
+                        "open",
+                        new byte[0],
+                        "{\"urgency\": \"low\", \"category\": \"x-pano.nowplaying\", \"transient\": true, \"suppressSound\": true, \"timeoutMs\": 5000}",
+                        0
+                );
+                System.out.println("notificationId: " + notificationId);
+                System.out.println("notificationCapabilities: " + getNotificationCapabilitiesLinux());
+                PanoNativeComponents.notify("Test Notification", "Updated in place", "", "open", new byte[0], "", notificationId);
 
                 // test tray icon
-                String menuItemIds[] = new String[] { "1", "2", "3", "Separator", "4" };
-                String menuItemTexts[] = new String[] { "📝 item_1", "item_2", "item_3", "", "item_4" };
+                String menuJson = "["
+                        + "{\"type\": \"standard\", \"id\": \"1\", \"label\": \"📝 item_1\", \"shortcut\": [[\"Control\", \"L\"]]},"
+                        + "{\"type\": \"checkmark\", \"id\": \"2\", \"label\": \"item_2\", \"checked\": true},"
+                        + "{\"type\": \"submenu\", \"label\": \"item_3\", \"children\": ["
+                        + "{\"type\": \"radio\", \"id\": \"3\", \"selected\": \"a\", \"options\": ["
+                        + "{\"id\": \"a\", \"label\": \"option_a\"}, {\"id\": \"b\", \"label\": \"option_b\"}]}]},"
+                        + "{\"type\": \"separator\"},"
+                        + "{\"type\": \"standard\", \"id\": \"4\", \"label\": \"item_4\", \"enabled\": false}"
+                        + "]";
 
                 int size = 8;
                 byte[] argb = new byte[size * size * 4];
@@ -112,7 +173,9 @@ public class PanoNativeComponents {
                     argb[i + 2] = (byte) 0xbe;
                     argb[i + 3] = (byte) 0xbe;
                 }
-                setTrayLinux("", argb, false, menuItemIds, menuItemTexts);
+                setTrayLinux("", argb, "", "auto", menuJson, true);
+                setTrayStatusLinux("active", "", new byte[0], "media-playback-pause", new byte[0]);
+                setTrayTooltipLinux("{\"albumArt\": true, \"progress\": true}");
 
                 // refreshSessions();
 
@@ -144,14 +207,39 @@ public class PanoNativeComponents {
         System.out.println("onPlaybackStateChanged: " + appId + ", " + state + ", " + position + ", " + canSkip);
     }
 
-    public static void onTrayMenuItemClicked(String id) {
-        System.out.println("onTrayMenuItemClicked: " + id);
+    public static void onTrayMenuItemClicked(String id, String value) {
+        System.out.println("onTrayMenuItemClicked: " + id + " " + value);
+    }
+
+    public static void onTrayAvailabilityChanged(boolean available) {
+        System.out.println("onTrayAvailabilityChanged: " + available);
+    }
+
+    public static void onTrayActivated(String kind, int x, int y, int delta) {
+        System.out.println("onTrayActivated: " + kind + " " + x + " " + y + " " + delta);
+    }
+
+    public static void onNotificationCapabilitiesChanged(boolean available, String[] capabilities, String serverName, String serverVendor, String serverVersion, String specVersion) {
+        System.out.println("onNotificationCapabilitiesChanged: " + available + ", " + Arrays.toString(capabilities) + ", " + serverName + ", " + serverVendor + ", " + serverVersion + ", " + specVersion);
+    }
+
+    public static void onNotificationAction(int id, String action) {
+        System.out.println("onNotificationAction: " + id + " " + action);
     }
 
     public static void onReceiveIpcCommand(String command, String arg) {
         System.out.println("onReceiveIpcCommand: " + command + " " + arg);
     }
 
+    public static void onDeepLink(String url, String path, String[] paramKeys, String[] paramValues) {
+        System.out.println("onDeepLink: " + url + ", " + path + ", " + Arrays.toString(paramKeys) + ", " + Arrays.toString(paramValues));
+    }
+
+    // userId, username and clientName are empty when disconnected
+    public static void onDiscordStatus(boolean connected, String userId, String username, String clientName) {
+        System.out.println("onDiscordStatus: " + connected + ", " + userId + ", " + username + ", " + clientName);
+    }
+
     public static void onDarkModeChange(boolean isDarkMode) {
         System.out.println("onDarkModeChange: " + isDarkMode);
     }
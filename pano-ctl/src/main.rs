@@ -0,0 +1,110 @@
+use std::process::ExitCode;
+
+use pano_ipc as ipc;
+use serde_json::{Value, json};
+
+const USAGE: &str = "Usage: pano-ctl [--profile <id>] <command>
+
+Commands:
+  status         print the current track
+  watch          print now playing events as they happen
+  love           love the current track
+  skip           skip to the next track
+  focus          bring the Pano Scrobbler window to the front
+  open <file>    open a file in Pano Scrobbler
+  list           list the running instances";
+
+const EXIT_OK: u8 = 0;
+const EXIT_NOT_RUNNING: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+fn print_result(result: Value) {
+    println!("{result}");
+}
+
+fn fail(code: u8, error: &str) -> ExitCode {
+    print_result(json!({ "ok": false, "error": error }));
+    ExitCode::from(code)
+}
+
+fn not_running(e: Box<dyn std::error::Error>) -> ExitCode {
+    fail(
+        EXIT_NOT_RUNNING,
+        &format!("Pano Scrobbler is not running: {e}"),
+    )
+}
+
+fn usage_error(error: &str) -> ExitCode {
+    eprintln!("{USAGE}");
+    fail(EXIT_USAGE, error)
+}
+
+fn send(command: &str, arg: &str) -> ExitCode {
+    match ipc::send_command(command, arg) {
+        Ok(_) => {
+            print_result(json!({ "ok": true }));
+            ExitCode::from(EXIT_OK)
+        }
+        Err(e) => not_running(e),
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    if let Some(i) = args.iter().position(|a| a == "--profile") {
+        if i + 1 >= args.len() {
+            return usage_error("--profile needs a value");
+        }
+
+        let profile_id = args.remove(i + 1);
+        args.remove(i);
+        ipc::set_profile_id(&profile_id);
+    }
+
+    let Some(command) = args.first() else {
+        return usage_error("no command given");
+    };
+
+    match (command.as_str(), &args[1..]) {
+        ("status", []) => match ipc::request(ipc::STATUS_COMMAND, "") {
+            Ok(reply) => {
+                let now_playing = serde_json::from_str::<Value>(&reply).unwrap_or(Value::Null);
+                print_result(json!({ "ok": true, "now_playing": now_playing }));
+                ExitCode::from(EXIT_OK)
+            }
+            Err(e) => not_running(e),
+        },
+
+        ("watch", []) => match ipc::watch(|line| println!("{line}")) {
+            Ok(_) => ExitCode::from(EXIT_OK),
+            Err(e) => not_running(e),
+        },
+
+        ("love", []) => send("love", ""),
+
+        ("skip", []) => send("skip", ""),
+
+        ("focus", []) => send("focus-existing", ""),
+
+        ("open", [file]) => {
+            // the running instance has a different working directory
+            match std::path::absolute(file) {
+                Ok(path) => send("open", &path.to_string_lossy()),
+                Err(e) => usage_error(&format!("invalid path {file}: {e}")),
+            }
+        }
+
+        ("list", []) => {
+            print_result(json!({ "ok": true, "instances": ipc::list_instances() }));
+            ExitCode::from(EXIT_OK)
+        }
+
+        ("help" | "--help" | "-h", _) => {
+            println!("{USAGE}");
+            ExitCode::from(EXIT_OK)
+        }
+
+        _ => usage_error(&format!("unknown command or arguments: {}", args.join(" "))),
+    }
+}
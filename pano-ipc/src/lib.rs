@@ -0,0 +1,276 @@
+use {
+    interprocess::local_socket::{GenericNamespaced, Name, ToNsName, traits::tokio::Stream},
+    std::{
+        collections::BTreeSet,
+        sync::{LazyLock, Mutex},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        time::timeout,
+    },
+};
+
+const PIPE_NAME_PREFIX: &str = "pano-scrobbler-ipc";
+const DEFAULT_PROFILE_ID: &str = "default";
+
+// keeps the connection open and streams now playing events as JSON lines
+pub const SUBSCRIBE_COMMAND: &str = "subscribe";
+// replies with the current session as a single JSON line, null if there is none
+pub const STATUS_COMMAND: &str = "status";
+
+static PROFILE_ID: LazyLock<Mutex<String>> =
+    LazyLock::new(|| Mutex::new(DEFAULT_PROFILE_ID.to_string()));
+
+#[cfg(target_os = "linux")]
+static USE_FILESYSTEM_SOCKET: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+// must be set before the listener starts, or commands will go to the old name
+pub fn set_profile_id(profile_id: &str) {
+    let profile_id = sanitize(profile_id);
+    *PROFILE_ID.lock().unwrap() = if profile_id.is_empty() {
+        DEFAULT_PROFILE_ID.to_string()
+    } else {
+        profile_id
+    };
+}
+
+// abstract sockets can be connected to by anything in the same network namespace,
+// a socket file in $XDG_RUNTIME_DIR is restricted by its permissions instead
+#[cfg(target_os = "linux")]
+pub fn set_use_filesystem_socket(enabled: bool) {
+    USE_FILESYSTEM_SOCKET.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+#[cfg(target_os = "linux")]
+pub fn use_filesystem_socket() -> bool {
+    USE_FILESYSTEM_SOCKET.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+pub fn profile_id() -> String {
+    PROFILE_ID.lock().unwrap().clone()
+}
+
+// keep names valid for both abstract sockets and named pipes,
+// and keep '.' free to be used as the separator
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn current_user() -> String {
+    let uid = unsafe { libc::getuid() };
+    uid.to_string()
+}
+
+#[cfg(target_os = "windows")]
+fn current_user() -> String {
+    sanitize(&std::env::var("USERNAME").unwrap_or_default())
+}
+
+fn user_prefix() -> String {
+    format!("{PIPE_NAME_PREFIX}.{}.", current_user())
+}
+
+fn pipe_name(profile_id: &str) -> String {
+    format!("{}{profile_id}", user_prefix())
+}
+
+#[cfg(target_os = "linux")]
+pub fn socket_path(profile_id: &str) -> Option<std::path::PathBuf> {
+    std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(|dir| std::path::PathBuf::from(dir).join(format!("{}.sock", pipe_name(profile_id))))
+}
+
+pub fn namespaced_name(profile_id: &str) -> std::io::Result<Name<'static>> {
+    pipe_name(profile_id).to_ns_name::<GenericNamespaced>()
+}
+
+/// Lists the profile IDs of the instances run by the current user, that are listening for commands.
+pub fn list_instances() -> Vec<String> {
+    let prefix = user_prefix();
+
+    listening_names()
+        .into_iter()
+        .filter_map(|name| name.strip_prefix(&prefix).map(|p| p.to_string()))
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn listening_names() -> BTreeSet<String> {
+    let mut names = listening_abstract_names();
+    names.extend(listening_filesystem_names());
+    names
+}
+
+#[cfg(target_os = "linux")]
+fn listening_filesystem_names() -> BTreeSet<String> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+
+    let Some(dir) = socket_path(DEFAULT_PROFILE_ID).and_then(|p| p.parent().map(|d| d.to_owned()))
+    else {
+        return BTreeSet::new();
+    };
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeSet::new();
+    };
+
+    let prefix = user_prefix();
+
+    // the directory is shared with other servers, like wayland or pipewire, which are never connected to.
+    // a crashed instance can leave its socket file behind, only count the ones that accept
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry
+                .file_name()
+                .to_str()?
+                .strip_suffix(".sock")?
+                .to_string();
+            name.starts_with(&prefix).then_some((entry, name))
+        })
+        .filter(|(entry, _)| entry.file_type().is_ok_and(|t| t.is_socket()))
+        .filter(|(entry, _)| UnixStream::connect(entry.path()).is_ok())
+        .map(|(_, name)| name)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn listening_abstract_names() -> BTreeSet<String> {
+    // abstract socket names show up in /proc/net/unix prefixed with @
+    // Num RefCount Protocol Flags Type St Inode Path
+    const SO_ACCEPTCON: u32 = 0x10000;
+
+    let contents = match std::fs::read_to_string("/proc/net/unix") {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to read /proc/net/unix: {e}");
+            return BTreeSet::new();
+        }
+    };
+
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols = line.split_whitespace().collect::<Vec<_>>();
+            let flags = u32::from_str_radix(cols.get(3)?, 16).ok()?;
+            let path = cols.get(7)?.strip_prefix('@')?;
+
+            if flags & SO_ACCEPTCON != 0 {
+                Some(path.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn listening_names() -> BTreeSet<String> {
+    match std::fs::read_dir(r"\\.\pipe\") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(e) => {
+            log::error!("Failed to list named pipes: {e}");
+            BTreeSet::new()
+        }
+    }
+}
+
+async fn connect() -> Result<interprocess::local_socket::tokio::Stream, Box<dyn std::error::Error>>
+{
+    let profile_id = profile_id();
+
+    // the server may have been started with either kind of socket, prefer the file if it exists.
+    // the file outlives the process, so one left by an earlier run falls back to the abstract name
+    #[cfg(target_os = "linux")]
+    if let Some(path) = socket_path(&profile_id).filter(|p| p.exists()) {
+        use interprocess::local_socket::{GenericFilePath, ToFsName};
+
+        match connect_to(path.as_path().to_fs_name::<GenericFilePath>()?).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => log::debug!("Failed to connect to {}: {e}", path.display()),
+        }
+    }
+
+    connect_to(namespaced_name(&profile_id)?).await
+}
+
+async fn connect_to(
+    name: Name<'_>,
+) -> Result<interprocess::local_socket::tokio::Stream, Box<dyn std::error::Error>> {
+    match timeout(
+        Duration::from_millis(500),
+        interprocess::local_socket::tokio::Stream::connect(name),
+    )
+    .await
+    {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) => Err(Box::from(e)),
+        Err(e) => Err(Box::from(e)),
+    }
+}
+
+async fn write_command(
+    conn: &mut (impl AsyncWriteExt + Unpin),
+    command: &str,
+    arg: &str,
+) -> std::io::Result<()> {
+    conn.write_all(command.as_bytes()).await?;
+    conn.write_all(b" ").await?;
+    conn.write_all(arg.as_bytes()).await?;
+
+    conn.write_all(b"\n").await
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn send_command(command: &str, arg: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = connect().await?;
+
+    write_command(&mut conn, command, arg).await?;
+
+    Ok(())
+}
+
+/// Sends a command that the listener replies to, and returns the reply line.
+#[tokio::main(flavor = "current_thread")]
+pub async fn request(command: &str, arg: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut conn = BufReader::new(connect().await?);
+
+    write_command(conn.get_mut(), command, arg).await?;
+
+    let mut reply = String::new();
+    timeout(Duration::from_secs(2), conn.read_line(&mut reply)).await??;
+
+    Ok(reply.trim_end().to_string())
+}
+
+/// Subscribes to now playing events, and calls `on_event` with each JSON line until the listener exits.
+#[tokio::main(flavor = "current_thread")]
+pub async fn watch(mut on_event: impl FnMut(&str)) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = BufReader::new(connect().await?);
+
+    write_command(conn.get_mut(), SUBSCRIBE_COMMAND, "").await?;
+
+    let mut lines = conn.lines();
+    while let Some(line) = lines.next_line().await? {
+        on_event(&line);
+    }
+
+    Ok(())
+}
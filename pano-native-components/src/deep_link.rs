@@ -0,0 +1,70 @@
+use url::Url;
+
+use crate::{ipc, jni_callback::JniCallback};
+
+pub const SCHEME: &str = "pano-scrobbler";
+
+// forwarded links arrive at the running instance as "deep-link <url>"
+const DEEP_LINK_COMMAND: &str = "deep-link";
+
+#[derive(Debug, Clone)]
+pub struct DeepLink {
+    pub url: String,
+    // host and path joined, e.g. "auth/lastfm" for pano-scrobbler://auth/lastfm?token=x
+    pub path: String,
+    pub params: Vec<(String, String)>,
+}
+
+pub fn parse(url: &str) -> Option<DeepLink> {
+    let parsed = match Url::parse(url.trim()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log::error!("Invalid deep link {url}: {e}");
+            return None;
+        }
+    };
+
+    if parsed.scheme() != SCHEME {
+        log::error!("Not a {SCHEME} deep link: {url}");
+        return None;
+    }
+
+    let path = format!("{}{}", parsed.host_str().unwrap_or_default(), parsed.path())
+        .trim_matches('/')
+        .to_string();
+
+    let params = parsed
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    Some(DeepLink {
+        url: parsed.to_string(),
+        path,
+        params,
+    })
+}
+
+/// Sends the link to an already running instance. Returns false if the link is invalid or nothing is running.
+pub fn forward(url: &str) -> bool {
+    let Some(deep_link) = parse(url) else {
+        return false;
+    };
+
+    match ipc::send_command(DEEP_LINK_COMMAND, &deep_link.url) {
+        Ok(_) => true,
+        Err(e) => {
+            log::debug!("Could not forward deep link: {e}");
+            false
+        }
+    }
+}
+
+// deep links get their own callback, everything else goes to onReceiveIpcCommand
+pub fn ipc_command_to_callback(command: String, arg: String) -> Option<JniCallback> {
+    if command == DEEP_LINK_COMMAND {
+        parse(&arg).map(JniCallback::DeepLink)
+    } else {
+        Some(JniCallback::IpcCallback(command, arg))
+    }
+}
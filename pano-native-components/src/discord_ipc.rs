@@ -0,0 +1,310 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use interprocess::local_socket::{
+    GenericFilePath, ToFsName,
+    tokio::{RecvHalf, SendHalf, Stream},
+    traits::tokio::Stream as _,
+};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+    task::JoinHandle,
+    time::timeout,
+};
+
+// https://github.com/discord/discord-rpc/blob/master/src/rpc_connection.h
+pub const OP_HANDSHAKE: u32 = 0;
+pub const OP_FRAME: u32 = 1;
+pub const OP_CLOSE: u32 = 2;
+pub const OP_PING: u32 = 3;
+pub const OP_PONG: u32 = 4;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// replies are small json objects, anything larger is not from Discord
+const MAX_FRAME_SIZE: u32 = 64 * 1024;
+
+#[cfg(target_os = "linux")]
+const ENV_KEYS: [&str; 4] = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"];
+
+// sandboxed clients put their socket in a subdirectory of the runtime dir.
+// a None client name means it is identified by the process on the other end
+#[cfg(target_os = "linux")]
+const SOCKET_LOCATIONS: [(&str, Option<&str>); 9] = [
+    ("", None),
+    ("app/com.discordapp.Discord/", Some("Discord (Flatpak)")),
+    (
+        "app/com.discordapp.DiscordCanary/",
+        Some("Discord Canary (Flatpak)"),
+    ),
+    ("app/dev.vencord.Vesktop/", Some("Vesktop (Flatpak)")),
+    (
+        ".flatpak/com.discordapp.Discord/xdg-run/",
+        Some("Discord (Flatpak)"),
+    ),
+    (
+        ".flatpak/dev.vencord.Vesktop/xdg-run/",
+        Some("Vesktop (Flatpak)"),
+    ),
+    ("snap.discord/", Some("Discord (Snap)")),
+    ("snap.discord-canary/", Some("Discord Canary (Snap)")),
+    ("snap.vesktop/", Some("Vesktop (Snap)")),
+];
+
+// the user arRPC reports in its READY event
+const ARRPC_USER_ID: &str = "1045800378228281345";
+
+const DEFAULT_CLIENT_NAME: &str = "Discord";
+
+pub type Frame = (u32, Value);
+
+/// A handshaken connection to the Discord client. Frames are read by a separate task,
+/// so that a closed socket is noticed even when nothing is being sent.
+pub struct DiscordConnection {
+    pub client_id: String,
+    // which Discord client is on the other end, e.g. "Vesktop (Flatpak)"
+    pub client_name: String,
+    // the logged in account, from the READY event
+    pub user_id: String,
+    pub username: String,
+    send_half: SendHalf,
+    frames_rx: mpsc::Receiver<Result<Frame, Box<dyn Error + Send + Sync>>>,
+    reader: JoinHandle<()>,
+    nonce: u64,
+}
+
+#[cfg(target_os = "linux")]
+fn base_dirs() -> Vec<PathBuf> {
+    let mut dirs = ENV_KEYS
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    // XDG_RUNTIME_DIR is missing in some session types
+    let runtime_dir = PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() }));
+    if !dirs.contains(&runtime_dir) {
+        dirs.push(runtime_dir);
+    }
+
+    dirs
+}
+
+#[cfg(target_os = "linux")]
+fn socket_paths() -> Vec<(PathBuf, Option<&'static str>)> {
+    let mut paths = vec![];
+
+    for base_dir in base_dirs() {
+        for i in 0..10 {
+            for (subpath, client_name) in SOCKET_LOCATIONS {
+                let path = base_dir.join(subpath).join(format!("discord-ipc-{i}"));
+
+                if path.exists() {
+                    paths.push((path, client_name));
+                }
+            }
+        }
+    }
+
+    paths
+}
+
+#[cfg(target_os = "windows")]
+fn socket_paths() -> Vec<(PathBuf, Option<&'static str>)> {
+    (0..10)
+        .map(|i| (PathBuf::from(format!(r"\\.\pipe\discord-ipc-{i}")), None))
+        .collect()
+}
+
+// names the client from the executable of the process that owns the socket
+#[cfg(target_os = "linux")]
+fn peer_client_name(stream: &Stream) -> Option<String> {
+    use interprocess::local_socket::traits::StreamCommon;
+
+    let pid = stream.peer_creds().ok()?.pid()?;
+    let comm = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    let comm = comm.trim().to_lowercase();
+
+    let client_name = if comm.contains("vesktop") {
+        "Vesktop"
+    } else if comm.contains("canary") {
+        "Discord Canary"
+    } else if comm.contains("ptb") {
+        "Discord PTB"
+    } else if comm.contains("arrpc") {
+        "arRPC"
+    } else {
+        DEFAULT_CLIENT_NAME
+    };
+
+    Some(client_name.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn peer_client_name(_stream: &Stream) -> Option<String> {
+    None
+}
+
+async fn write_frame(
+    send_half: &mut SendHalf,
+    op: u32,
+    data: &Value,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let data = data.to_string();
+    let mut frame = Vec::with_capacity(8 + data.len());
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    frame.extend_from_slice(data.as_bytes());
+
+    send_half.write_all(&frame).await?;
+    Ok(())
+}
+
+async fn read_frame(recv_half: &mut RecvHalf) -> Result<Frame, Box<dyn Error + Send + Sync>> {
+    let mut header = [0u8; 8];
+    recv_half.read_exact(&mut header).await?;
+
+    let op = u32::from_le_bytes(header[..4].try_into()?);
+    let len = u32::from_le_bytes(header[4..].try_into()?);
+
+    if len > MAX_FRAME_SIZE {
+        return Err(format!("Discord IPC frame too large: {len} bytes").into());
+    }
+
+    let mut data = vec![0u8; len as usize];
+    recv_half.read_exact(&mut data).await?;
+
+    Ok((op, serde_json::from_slice(&data)?))
+}
+
+impl DiscordConnection {
+    /// Tries every known socket location until one accepts the handshake.
+    pub async fn connect(client_id: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut last_error: Box<dyn Error + Send + Sync> = "Discord IPC socket not found".into();
+
+        for (path, client_name) in socket_paths() {
+            match Self::connect_to(&path, client_name, client_id).await {
+                Ok(connection) => {
+                    log::debug!(
+                        "Connected to {} at {}",
+                        connection.client_name,
+                        path.display()
+                    );
+                    return Ok(connection);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error)
+    }
+
+    async fn connect_to(
+        path: &Path,
+        client_name: Option<&str>,
+        client_id: &str,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let stream = Stream::connect(path.to_fs_name::<GenericFilePath>()?).await?;
+
+        let mut client_name = client_name
+            .map(|client_name| client_name.to_string())
+            .or_else(|| peer_client_name(&stream))
+            .unwrap_or_else(|| DEFAULT_CLIENT_NAME.to_string());
+
+        let (mut recv_half, mut send_half) = stream.split();
+
+        let handshake = json!({ "v": 1, "client_id": client_id });
+        write_frame(&mut send_half, OP_HANDSHAKE, &handshake).await?;
+
+        let (op, data) = timeout(HANDSHAKE_TIMEOUT, read_frame(&mut recv_half)).await??;
+
+        if op == OP_CLOSE || data["evt"] != "READY" {
+            return Err(format!("Discord rejected the handshake: {data}").into());
+        }
+
+        let user = &data["data"]["user"];
+        let user_id = user["id"].as_str().unwrap_or_default().to_string();
+        let username = user["username"].as_str().unwrap_or_default().to_string();
+
+        // arRPC runs under node or bun, so it can only be told apart by its fake user
+        if user_id == ARRPC_USER_ID || username == "arRPC" {
+            client_name = "arRPC".to_string();
+        }
+
+        let (frames_tx, frames_rx) = mpsc::channel(8);
+
+        let reader = tokio::spawn(async move {
+            loop {
+                let frame = read_frame(&mut recv_half).await;
+                let is_err = frame.is_err();
+
+                if frames_tx.send(frame).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            client_id: client_id.to_string(),
+            client_name,
+            user_id,
+            username,
+            send_half,
+            frames_rx,
+            reader,
+            nonce: 0,
+        })
+    }
+
+    /// Waits for the next frame from Discord. Cancel safe.
+    pub async fn next_frame(&mut self) -> Result<Frame, Box<dyn Error + Send + Sync>> {
+        self.frames_rx
+            .recv()
+            .await
+            .unwrap_or_else(|| Err("Discord connection closed".into()))
+    }
+
+    pub async fn send(
+        &mut self,
+        op: u32,
+        data: &Value,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        write_frame(&mut self.send_half, op, data).await
+    }
+
+    // pass Value::Null to clear the activity
+    pub async fn set_activity(
+        &mut self,
+        activity: Value,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.nonce += 1;
+
+        let data = json!({
+            "cmd": "SET_ACTIVITY",
+            "args": {
+                "pid": std::process::id(),
+                "activity": activity,
+            },
+            "nonce": self.nonce.to_string(),
+        });
+
+        self.send(OP_FRAME, &data).await
+    }
+
+    pub async fn close(mut self) {
+        let _ = self.send(OP_CLOSE, &json!({})).await;
+        let _ = self.send_half.shutdown().await;
+    }
+}
+
+impl Drop for DiscordConnection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
@@ -1,37 +1,118 @@
-use std::sync::{Mutex, OnceLock};
+use std::{
+    collections::VecDeque,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::Duration,
+};
 
-use discord_rich_presence::{
-    DiscordIpc, DiscordIpcClient,
-    activity::{self},
-    error,
+use discord_rich_presence::activity::{self};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    sync::mpsc,
+    time::{Instant, sleep_until},
 };
 
-#[derive(Debug)]
+use crate::discord_ipc::{DiscordConnection, OP_CLOSE, OP_FRAME, OP_PING, OP_PONG};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiscordActivityType {
+    #[default]
+    Listening,
+    Watching,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordButton {
+    pub label: String,
+    pub url: String,
+}
+
+// deserialized from the json passed to updateDiscordActivity, missing fields get the defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct DiscordActivity {
     pub client_id: String,
+    pub activity_type: DiscordActivityType,
     pub name: String,
     pub state: String,
+    pub state_url: String,
     pub details: String,
+    pub details_url: String,
+    pub art_url: String,
     pub large_text: String,
+    pub large_url: String,
+    pub playing_small_image: String,
+    pub playing_small_text: String,
+    pub paused_small_image: String,
+    pub paused_small_text: String,
     pub start_time: i64,
     pub end_time: Option<i64>,
-    pub details_url: String,
-    pub art_url: String,
     pub status_line: i32,
     pub is_playing: bool,
-    pub button_text: String,
-    pub button_url: String,
+    // only the first two are shown by Discord
+    pub buttons: Vec<DiscordButton>,
+    // [current, max], e.g. the track number and the number of tracks in the album
+    pub party_size: Option<[i32; 2]>,
+}
+
+impl Default for DiscordActivity {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            activity_type: DiscordActivityType::default(),
+            name: String::new(),
+            state: String::new(),
+            state_url: String::new(),
+            details: String::new(),
+            details_url: String::new(),
+            art_url: String::new(),
+            large_text: String::new(),
+            large_url: String::new(),
+            playing_small_image: String::new(),
+            playing_small_text: String::new(),
+            paused_small_image: "pause_circle".to_string(),
+            paused_small_text: "Paused".to_string(),
+            start_time: 0,
+            end_time: None,
+            status_line: 0,
+            is_playing: true,
+            buttons: vec![],
+            party_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscordStatus {
+    pub connected: bool,
+    pub user_id: String,
+    pub username: String,
+    pub client_name: String,
+}
+
+#[derive(Debug)]
+enum DiscordCommand {
+    Update(Box<DiscordActivity>),
+    Clear(bool),
 }
 
-static CLIENT: OnceLock<Mutex<DiscordIpcClient>> = OnceLock::new();
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// Discord allows about 5 SET_ACTIVITY calls per 20 seconds and drops the rest
+const RATE_LIMIT_COUNT: usize = 5;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(20);
 
-// pass None to clear activity
-pub fn update(da: DiscordActivity) -> Result<(), error::Error> {
-    let mut client = CLIENT
-        .get_or_init(|| Mutex::new(DiscordIpcClient::new(&da.client_id)))
-        .lock()
-        .unwrap();
+static COMMAND_TX: OnceLock<mpsc::UnboundedSender<DiscordCommand>> = OnceLock::new();
 
+static STATUS: LazyLock<Mutex<DiscordStatus>> =
+    LazyLock::new(|| Mutex::new(DiscordStatus::default()));
+
+const MAX_BUTTONS: usize = 2;
+
+fn build_activity(da: &DiscordActivity) -> Value {
     let mut assets = activity::Assets::new();
 
     if da.art_url.is_empty() {
@@ -44,18 +125,37 @@ pub fn update(da: DiscordActivity) -> Result<(), error::Error> {
         assets = assets.large_text(&da.large_text);
     }
 
-    if !da.is_playing {
-        assets = assets.small_image("pause_circle").small_text("Paused")
+    if !da.large_url.is_empty() {
+        assets = assets.large_url(&da.large_url);
+    }
+
+    let (small_image, small_text) = if da.is_playing {
+        (&da.playing_small_image, &da.playing_small_text)
+    } else {
+        (&da.paused_small_image, &da.paused_small_text)
     };
 
+    if !small_image.is_empty() {
+        assets = assets.small_image(small_image);
+
+        if !small_text.is_empty() {
+            assets = assets.small_text(small_text);
+        }
+    }
+
     let status_display_type = match da.status_line {
         2 => activity::StatusDisplayType::State,
         1 => activity::StatusDisplayType::Details,
         _ => activity::StatusDisplayType::Name,
     };
 
+    let activity_type = match da.activity_type {
+        DiscordActivityType::Listening => activity::ActivityType::Listening,
+        DiscordActivityType::Watching => activity::ActivityType::Watching,
+    };
+
     let mut activity = activity::Activity::new()
-        .activity_type(activity::ActivityType::Listening)
+        .activity_type(activity_type)
         .state(&da.state)
         .details(&da.details)
         .status_display_type(status_display_type)
@@ -65,59 +165,320 @@ pub fn update(da: DiscordActivity) -> Result<(), error::Error> {
         activity = activity.name(&da.name);
     }
 
+    if !da.state_url.is_empty() {
+        activity = activity.state_url(&da.state_url);
+    }
+
     if !da.details_url.is_empty() {
         activity = activity.details_url(&da.details_url);
     }
 
-    if !da.button_text.is_empty() && !da.button_url.is_empty() {
-        let buttons = vec![activity::Button::new(&da.button_text, &da.button_url)];
+    let buttons = da
+        .buttons
+        .iter()
+        .filter(|b| !b.label.is_empty() && !b.url.is_empty())
+        .take(MAX_BUTTONS)
+        .map(|b| activity::Button::new(&b.label, &b.url))
+        .collect::<Vec<_>>();
+
+    if !buttons.is_empty() {
         activity = activity.buttons(buttons);
     }
 
+    if let Some(party_size) = da.party_size {
+        activity = activity.party(activity::Party::new().size(party_size));
+    }
+
     let mut ts = activity::Timestamps::new().start(da.start_time);
-    if let Some(end_time) = da.end_time {
+    if let Some(end_time) = da.end_time.filter(|end_time| *end_time > 0) {
         ts = ts.end(end_time);
     }
     activity = activity.timestamps(ts);
 
-    let send_result = client.set_activity(activity.clone());
+    serde_json::to_value(activity).unwrap_or_default()
+}
+
+pub fn status() -> DiscordStatus {
+    STATUS.lock().unwrap().clone()
+}
+
+/// Starts the presence worker on its own thread, if it is not running yet.
+/// on_status_changed is called whenever the connection to Discord is established or lost.
+pub fn start_worker(on_status_changed: impl Fn(&DiscordStatus) + Send + 'static) {
+    COMMAND_TX.get_or_init(|| {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        std::thread::spawn(move || {
+            if let Err(e) = worker(rx, on_status_changed) {
+                log::error!("Discord worker stopped: {e}");
+            }
+        });
+
+        tx
+    });
+}
+
+fn send_command(command: DiscordCommand) -> bool {
+    match COMMAND_TX.get() {
+        Some(tx) => tx.send(command).is_ok(),
+        None => {
+            log::error!("Discord worker not started, did not send {command:?}");
+            false
+        }
+    }
+}
+
+pub fn update(da: DiscordActivity) -> bool {
+    send_command(DiscordCommand::Update(Box::new(da)))
+}
+
+pub fn clear(shutdown: bool) -> bool {
+    send_command(DiscordCommand::Clear(shutdown))
+}
+
+struct Worker<F: Fn(&DiscordStatus)> {
+    connection: Option<DiscordConnection>,
+    // what should be shown, replayed after every reconnect
+    desired: Option<DiscordActivity>,
+    // desired has changed since it was last sent
+    dirty: bool,
+    // send times within the last RATE_LIMIT_WINDOW
+    sent_at: VecDeque<Instant>,
+    // when a rate limited update can go out
+    flush_at: Option<Instant>,
+    reconnect_at: Option<Instant>,
+    reconnect_delay: Duration,
+    on_status_changed: F,
+}
+
+impl<F: Fn(&DiscordStatus)> Worker<F> {
+    fn set_status(&self, status: DiscordStatus) {
+        *STATUS.lock().unwrap() = status.clone();
+        (self.on_status_changed)(&status);
+    }
+
+    async fn disconnect(&mut self, clear_activity: bool) {
+        if let Some(mut connection) = self.connection.take() {
+            if clear_activity {
+                let _ = connection.set_activity(Value::Null).await;
+            }
 
-    match send_result {
-        Ok(_) => {}
-        Err(error::Error::NotConnected) => {
-            client.connect()?;
-            client.set_activity(activity)?;
+            connection.close().await;
+            self.set_status(DiscordStatus::default());
         }
-        Err(error::Error::IPCConnectionFailed) => {
-            client.reconnect()?;
-            client.set_activity(activity)?;
+    }
+
+    // a lost connection is retried with an increasing delay, as long as there is something to show
+    fn connection_lost(&mut self) {
+        if self.connection.take().is_some() {
+            self.set_status(DiscordStatus::default());
         }
-        Err(error::Error::WriteError(_)) => {
-            client.reconnect()?;
-            client.set_activity(activity)?;
+
+        // the reconnect sends whatever is desired by then
+        self.flush_at = None;
+
+        if self.desired.is_some() {
+            self.reconnect_at = Some(Instant::now() + self.reconnect_delay);
+            self.reconnect_delay = (self.reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        } else {
+            self.reconnect_at = None;
         }
+    }
+
+    async fn connect(&mut self) {
+        self.reconnect_at = None;
 
-        Err(e) => {
-            log::error!("Failed to set Discord activity: {e}");
+        let Some(client_id) = self.desired.as_ref().map(|da| da.client_id.clone()) else {
+            return;
+        };
+
+        match DiscordConnection::connect(&client_id).await {
+            Ok(connection) => {
+                let status = DiscordStatus {
+                    connected: true,
+                    user_id: connection.user_id.clone(),
+                    username: connection.username.clone(),
+                    client_name: connection.client_name.clone(),
+                };
+                self.connection = Some(connection);
+                self.reconnect_delay = MIN_RECONNECT_DELAY;
+                self.set_status(status);
+                self.dirty = true;
+                self.flush().await;
+            }
+            Err(e) => {
+                log::debug!("Could not connect to Discord: {e}");
+                self.connection_lost();
+            }
         }
     }
 
-    Ok(())
-}
+    // sends the latest desired activity if the rate limit allows it, otherwise schedules it.
+    // anything that changes in the meantime replaces it, so intermediate states are never sent
+    async fn flush(&mut self) {
+        let Some(connection) = self.connection.as_mut() else {
+            return;
+        };
+
+        if !self.dirty {
+            return;
+        }
+
+        let now = Instant::now();
+
+        while self
+            .sent_at
+            .front()
+            .is_some_and(|sent_at| now.duration_since(*sent_at) >= RATE_LIMIT_WINDOW)
+        {
+            self.sent_at.pop_front();
+        }
+
+        if self.sent_at.len() >= RATE_LIMIT_COUNT {
+            self.flush_at = self
+                .sent_at
+                .front()
+                .map(|sent_at| *sent_at + RATE_LIMIT_WINDOW);
+            return;
+        }
+
+        self.flush_at = None;
+
+        let activity = self
+            .desired
+            .as_ref()
+            .map(build_activity)
+            .unwrap_or(Value::Null);
+
+        match connection.set_activity(activity).await {
+            Ok(_) => {
+                self.dirty = false;
+                self.sent_at.push_back(now);
+            }
+            Err(e) => {
+                log::error!("Failed to set Discord activity: {e}");
+                self.connection_lost();
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, command: DiscordCommand) {
+        match command {
+            DiscordCommand::Update(da) => {
+                // switching applications clears the old one's activity
+                if let Some(connection) = &self.connection
+                    && connection.client_id != da.client_id
+                {
+                    self.disconnect(true).await;
+                }
+
+                self.desired = Some(*da);
+                self.dirty = true;
+
+                if self.connection.is_some() {
+                    self.flush().await;
+                } else if self.reconnect_at.is_none() {
+                    self.connect().await;
+                }
+            }
+
+            DiscordCommand::Clear(shutdown) => {
+                self.desired = None;
+                self.dirty = true;
+                self.reconnect_at = None;
+
+                if shutdown {
+                    self.flush_at = None;
+                    self.disconnect(true).await;
+                } else {
+                    self.flush().await;
+                }
+            }
+        }
+    }
+
+    async fn handle_frame(&mut self, op: u32, data: Value) {
+        match op {
+            OP_PING => {
+                if let Some(connection) = self.connection.as_mut()
+                    && connection.send(OP_PONG, &data).await.is_err()
+                {
+                    self.connection_lost();
+                }
+            }
+
+            OP_CLOSE => {
+                log::debug!("Discord closed the connection: {data}");
+                self.connection_lost();
+            }
 
-pub fn clear(shutdown: bool) -> Result<(), error::Error> {
-    let client = CLIENT.get();
+            OP_FRAME if data["evt"] == "ERROR" => {
+                log::error!("Discord error: {}", data["data"]);
+            }
 
-    if client.is_none() {
-        return Err(error::Error::NotConnected);
+            _ => {}
+        }
     }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn worker(
+    mut rx: mpsc::UnboundedReceiver<DiscordCommand>,
+    on_status_changed: impl Fn(&DiscordStatus),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut worker = Worker {
+        connection: None,
+        desired: None,
+        dirty: false,
+        sent_at: VecDeque::new(),
+        flush_at: None,
+        reconnect_at: None,
+        reconnect_delay: MIN_RECONNECT_DELAY,
+        on_status_changed,
+    };
+
+    loop {
+        let reconnect_at = worker.reconnect_at;
+        let flush_at = worker.flush_at;
+
+        tokio::select! {
+            command = rx.recv() => {
+                let Some(command) = command else {
+                    break;
+                };
+                worker.handle_command(command).await;
+            }
 
-    let mut client = client.unwrap().lock().unwrap();
-    let _ = client.clear_activity();
+            frame = async {
+                match worker.connection.as_mut() {
+                    Some(connection) => connection.next_frame().await,
+                    None => std::future::pending().await,
+                }
+            } => match frame {
+                Ok((op, data)) => worker.handle_frame(op, data).await,
+                Err(e) => {
+                    log::debug!("Discord connection lost: {e}");
+                    worker.connection_lost();
+                }
+            },
 
-    if shutdown {
-        client.close()?;
+            _ = async {
+                match reconnect_at {
+                    Some(reconnect_at) => sleep_until(reconnect_at).await,
+                    None => std::future::pending().await,
+                }
+            } => worker.connect().await,
+
+            _ = async {
+                match flush_at {
+                    Some(flush_at) => sleep_until(flush_at).await,
+                    None => std::future::pending().await,
+                }
+            } => worker.flush().await,
+        }
     }
 
+    worker.disconnect(true).await;
+
     Ok(())
 }
@@ -1,26 +1,96 @@
 use {
     interprocess::local_socket::{
-        GenericNamespaced, ListenerOptions, ToNsName,
+        ListenerOptions,
         traits::tokio::{Listener, Stream},
     },
-    std::time::Duration,
+    pano_ipc::{STATUS_COMMAND, SUBSCRIBE_COMMAND, namespaced_name, profile_id},
+    std::sync::Arc,
     tokio::{
         io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-        time::timeout,
+        sync::broadcast,
     },
 };
 
-const PIPE_NAME: &str = "pano-scrobbler-ipc";
+#[cfg(target_os = "linux")]
+use pano_ipc::socket_path;
 
-pub async fn commands_listener(
-    ipc_callback: impl Fn(String, String) + 'static,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let name = PIPE_NAME.to_ns_name::<GenericNamespaced>()?;
+#[cfg(target_os = "linux")]
+pub use pano_ipc::set_use_filesystem_socket;
+pub use pano_ipc::{list_instances, send_command, set_profile_id};
+
+use crate::now_playing::{self, NowPlayingEvent};
+
+fn create_listener(
+    profile_id: &str,
+) -> std::io::Result<interprocess::local_socket::tokio::Listener> {
+    #[cfg(target_os = "linux")]
+    if pano_ipc::use_filesystem_socket() {
+        use interprocess::{
+            local_socket::{GenericFilePath, ToFsName},
+            os::unix::local_socket::ListenerOptionsExt,
+        };
+
+        match socket_path(profile_id) {
+            Some(path) => {
+                remove_stale_socket(&path);
+
+                return ListenerOptions::new()
+                    .name(path.to_fs_name::<GenericFilePath>()?)
+                    .mode(0o600)
+                    .reclaim_name(true)
+                    .create_tokio();
+            }
+            None => log::error!("XDG_RUNTIME_DIR is not set, using an abstract socket"),
+        }
+    }
+
+    // clients prefer the file, so one left by an earlier run with the filesystem socket must go
+    #[cfg(target_os = "linux")]
+    if let Some(path) = socket_path(profile_id) {
+        remove_stale_socket(&path);
+    }
 
-    let listener = ListenerOptions::new()
-        .name(name)
+    ListenerOptions::new()
+        .name(namespaced_name(profile_id)?)
         .reclaim_name(true)
-        .create_tokio();
+        .create_tokio()
+}
+
+// removes a socket file left behind by a crash, but never one that is still in use
+#[cfg(target_os = "linux")]
+fn remove_stale_socket(path: &std::path::Path) {
+    if path.exists() && std::os::unix::net::UnixStream::connect(path).is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_peer_allowed(conn: &interprocess::local_socket::tokio::Stream) -> bool {
+    use interprocess::local_socket::traits::StreamCommon;
+
+    let uid = unsafe { libc::getuid() };
+
+    match conn.peer_creds() {
+        Ok(creds) if creds.euid() == Some(uid) => true,
+        Ok(creds) => {
+            log::error!(
+                "Rejected IPC connection from pid {:?}, uid {:?}",
+                creds.pid(),
+                creds.euid()
+            );
+            false
+        }
+        Err(e) => {
+            log::error!("Rejected IPC connection, could not get peer credentials: {e}");
+            false
+        }
+    }
+}
+
+pub async fn commands_listener(
+    ipc_callback: impl Fn(String, String) + Send + Sync + 'static,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = create_listener(&profile_id());
 
     let listener = match listener {
         Ok(l) => l,
@@ -30,64 +100,113 @@ pub async fn commands_listener(
         }
     };
 
-    let mut buffer = String::with_capacity(128);
+    let ipc_callback = Arc::new(ipc_callback);
 
     loop {
         match listener.accept().await {
             Ok(conn) => {
-                let mut conn = BufReader::new(conn);
+                #[cfg(target_os = "linux")]
+                if !is_peer_allowed(&conn) {
+                    continue;
+                }
+
+                tokio::spawn(handle_connection(conn, ipc_callback.clone()));
+            }
+            Err(e) => {
+                log::error!("There was an error with an incoming connection: {e}");
+                continue;
+            }
+        };
+    }
+}
+
+// one command per line, until the client disconnects
+async fn handle_connection(
+    conn: interprocess::local_socket::tokio::Stream,
+    ipc_callback: Arc<impl Fn(String, String) + Send + Sync>,
+) {
+    let (recv_half, mut send_half) = conn.split();
+    let mut lines = BufReader::new(recv_half).lines();
+    let mut events: Option<broadcast::Receiver<NowPlayingEvent>> = None;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::error!("Error reading from pipe: {e}");
+                        break;
+                    }
+                };
+
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
 
-                match conn.read_line(&mut buffer).await {
-                    Ok(_) => {
-                        let parts: Vec<&str> = buffer.splitn(2, ' ').collect();
-                        if parts.len() == 2 {
-                            let command = parts[0].trim();
-                            let arg = parts[1].trim();
+                let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
 
-                            ipc_callback(command.to_string(), arg.to_string());
+                match command {
+                    SUBSCRIBE_COMMAND => {
+                        if events.is_some() {
+                            continue;
                         }
 
-                        buffer.clear();
+                        // subscribe before taking the snapshot, so that nothing is missed in between
+                        events = Some(now_playing::subscribe());
+
+                        for event in now_playing::snapshot() {
+                            if write_event(&mut send_half, &event).await.is_err() {
+                                return;
+                            }
+                        }
                     }
-                    Err(e) => {
-                        log::error!("Error reading from pipe: {e}");
-                        buffer.clear();
+                    STATUS_COMMAND => {
+                        let mut json = serde_json::to_string(&now_playing::current())
+                            .unwrap_or_else(|_| "null".to_string());
+                        json.push('\n');
+
+                        if send_half.write_all(json.as_bytes()).await.is_err() {
+                            break;
+                        }
                     }
+                    _ => ipc_callback(command.to_string(), arg.trim().to_string()),
                 }
             }
-            Err(e) => {
-                log::error!("There was an error with an incoming connection: {e}");
-                continue;
+
+            event = next_event(&mut events) => {
+                match event {
+                    Ok(event) => {
+                        if write_event(&mut send_half, &event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::error!("IPC subscriber lagged behind, skipped {skipped} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
             }
-        };
+        }
     }
 }
 
-async fn connect() -> Result<interprocess::local_socket::tokio::Stream, Box<dyn std::error::Error>>
-{
-    let name = PIPE_NAME.to_ns_name::<GenericNamespaced>()?;
-
-    match timeout(
-        Duration::from_millis(500),
-        interprocess::local_socket::tokio::Stream::connect(name),
-    )
-    .await
-    {
-        Ok(Ok(stream)) => Ok(stream),
-        Ok(Err(e)) => Err(Box::from(e)),
-        Err(e) => Err(Box::from(e)),
+async fn next_event(
+    events: &mut Option<broadcast::Receiver<NowPlayingEvent>>,
+) -> Result<NowPlayingEvent, broadcast::error::RecvError> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
     }
 }
 
-#[tokio::main(flavor = "current_thread")]
-pub async fn send_command(command: &str, arg: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut conn = connect().await?;
-
-    conn.write_all(command.as_bytes()).await?;
-    conn.write_all(b" ").await?;
-    conn.write_all(arg.as_bytes()).await?;
-
-    conn.write_all(b"\n").await?;
-
-    Ok(())
+async fn write_event(
+    send_half: &mut (impl AsyncWriteExt + Unpin),
+    event: &NowPlayingEvent,
+) -> std::io::Result<()> {
+    let mut json = serde_json::to_string(event)?;
+    json.push('\n');
+    send_half.write_all(json.as_bytes()).await
 }
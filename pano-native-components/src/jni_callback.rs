@@ -1,14 +1,38 @@
+use crate::deep_link::DeepLink;
+use crate::discord_rpc::DiscordStatus;
+#[cfg(target_os = "linux")]
+use crate::media_events::NotificationCapabilities;
 use crate::media_events::{MetadataInfo, PlaybackInfo, SessionInfo};
+#[cfg(target_os = "linux")]
+use crate::tray::TrayActivation;
+
+// sent to java as the action when a notification goes away for any reason
+#[cfg(target_os = "linux")]
+pub const CLOSED_ACTION: &str = "closed";
 
 #[derive(Debug)]
 pub enum JniCallback {
     #[cfg(target_os = "linux")]
-    TrayItemClicked(String),
+    // item id and its new value: "true" or "false" for checkmarks,
+    // the selected option id for radio groups, empty for the rest
+    TrayItemClicked(String, String),
+    #[cfg(target_os = "linux")]
+    TrayActivated(TrayActivation),
+    #[cfg(target_os = "linux")]
+    TrayAvailabilityChanged(bool),
+    #[cfg(target_os = "linux")]
+    // notification id and the action id, the default action when clicked,
+    // or "closed" when it is dismissed, expires or is closed after an action
+    NotificationAction(u32, String),
+    #[cfg(target_os = "linux")]
+    NotificationCapabilitiesChanged(NotificationCapabilities),
     FilePicked(i32, String),
     SessionsChanged(Vec<SessionInfo>),
     MetadataChanged(String, MetadataInfo),
     PlaybackStateChanged(String, PlaybackInfo),
     IpcCallback(String, String),
+    DeepLink(DeepLink),
     DarkModeChanged(bool),
+    DiscordStatusChanged(DiscordStatus),
     IsAppIdAllowed(String),
 }
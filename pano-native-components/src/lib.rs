@@ -5,10 +5,20 @@ mod media_listener;
 mod file_picker;
 #[cfg(target_os = "linux")]
 mod tray;
+#[cfg(target_os = "linux")]
+mod tray_icon;
+#[cfg(target_os = "linux")]
+mod tray_tooltip;
 
+mod deep_link;
+mod discord_ipc;
 mod discord_rpc;
 mod ipc;
 mod jni_callback;
+mod notification_scheduler;
+mod now_playing;
+mod now_playing_file;
+mod now_playing_server;
 mod theme_observer;
 mod windows_utils;
 
@@ -27,16 +37,19 @@ use media_listener::listener;
 use tokio::sync::mpsc;
 
 use std::env;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{LazyLock, Mutex};
 
 use crate::discord_rpc::DiscordActivity;
-use crate::media_events::{MetadataInfo, PlaybackInfo};
+use crate::media_events::{MetadataInfo, NotificationInfo, NotificationOptions, PlaybackInfo};
 
 static INCOMING_PLAYER_EVENT_TX: LazyLock<Mutex<Option<mpsc::Sender<IncomingEvent>>>> =
     LazyLock::new(|| Mutex::new(None));
 
 static HWND: LazyLock<Mutex<Option<i64>>> = LazyLock::new(|| Mutex::new(None));
 
+static NEXT_NOTIFICATION_ID: AtomicU32 = AtomicU32::new(1);
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_refreshSessions(
     _env: EnvUnowned,
@@ -146,18 +159,122 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_unmute(
 }
 
 #[unsafe(no_mangle)]
+// actions_json is an array of {"id", "label"} buttons. these, image_bytes and options_json
+// (see NotificationOptions) are only used on linux.
+// pass a previously returned id as replaces_id to update that notification in place, or 0.
+// returns the id that onNotificationAction and closeNotification take
 pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_notify(
     mut unowned_env: EnvUnowned,
     _class: JClass,
     title: JString,
     body: JString,
+    actions_json: JString,
+    default_action: JString,
+    image_bytes: JByteArray,
+    options_json: JString,
+    replaces_id: jint,
+) -> jint {
+    unowned_env
+        .with_env(|env| -> jni::errors::Result<jint> {
+            let id = if replaces_id > 0 {
+                replaces_id as u32
+            } else {
+                NEXT_NOTIFICATION_ID.fetch_add(1, Ordering::Relaxed)
+            };
+
+            let actions_json: String = actions_json.mutf8_chars(env)?.into();
+            let actions = if actions_json.is_empty() {
+                vec![]
+            } else {
+                serde_json::from_str(&actions_json).unwrap_or_else(|e| {
+                    log::error!("Invalid notification actions: {e}");
+                    vec![]
+                })
+            };
+
+            let options_json: String = options_json.mutf8_chars(env)?.into();
+            let options = if options_json.is_empty() {
+                NotificationOptions::default()
+            } else {
+                serde_json::from_str(&options_json).unwrap_or_else(|e| {
+                    log::error!("Invalid notification options: {e}");
+                    NotificationOptions::default()
+                })
+            };
+
+            notification_scheduler::notify(NotificationInfo {
+                id,
+                title: title.mutf8_chars(env)?.into(),
+                body: body.mutf8_chars(env)?.into(),
+                actions,
+                default_action: default_action.mutf8_chars(env)?.into(),
+                image_bytes: env.convert_byte_array(&image_bytes)?,
+                options,
+            });
+            Ok(id as jint)
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_closeNotification(
+    _env: EnvUnowned,
+    _class: JClass,
+    id: jint,
 ) {
+    notification_scheduler::close(id as u32);
+}
+
+// limits how often notifications of a category are shown, and summarizes bursts.
+// see NotificationRateLimits, pass an empty string to remove all limits
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setNotificationRateLimits(
+    mut unowned_env: EnvUnowned,
+    _class: JClass,
+    config_json: JString,
+) -> jboolean {
     unowned_env
-        .with_env(|env| -> jni::errors::Result<()> {
-            let title: String = title.mutf8_chars(env)?.into();
-            let body: String = body.mutf8_chars(env)?.into();
-            send_incoming_event(IncomingEvent::Notification(title, body));
-            Ok(())
+        .with_env(|env| -> jni::errors::Result<jboolean> {
+            let config_json: String = config_json.mutf8_chars(env)?.into();
+
+            if config_json.is_empty() {
+                notification_scheduler::set_rate_limits(Default::default());
+                return Ok(true);
+            }
+
+            match serde_json::from_str(&config_json) {
+                Ok(rate_limits) => {
+                    notification_scheduler::set_rate_limits(rate_limits);
+                    Ok(true)
+                }
+                Err(e) => {
+                    log::error!("Invalid notification rate limits {config_json}: {e}");
+                    Ok(false)
+                }
+            }
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+}
+
+// json of NotificationCapabilities, empty on other platforms.
+// onNotificationCapabilitiesChanged is called whenever the notification server changes
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_getNotificationCapabilitiesLinux<
+    'local,
+>(
+    mut unowned_env: EnvUnowned<'local>,
+    _class: JClass<'local>,
+) -> JString<'local> {
+    unowned_env
+        .with_env(|env| -> jni::errors::Result<JString<'_>> {
+            #[cfg(target_os = "linux")]
+            let capabilities = serde_json::to_string(&media_listener::notification_capabilities())
+                .unwrap_or_default();
+
+            #[cfg(not(target_os = "linux"))]
+            let capabilities = String::new();
+
+            JString::from_str(env, capabilities)
         })
         .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
 }
@@ -167,10 +284,11 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setTrayLinux(
     mut unowned_env: EnvUnowned,
     _class: JClass,
     tooltip: JString,
-    png_bytes: JByteArray,
-    invert: jboolean,
-    menu_item_ids: JObjectArray<JString>,
-    menu_item_texts: JObjectArray<JString>,
+    icon_bytes: JByteArray,
+    icon_name: JString,
+    icon_color: JString,
+    menu_json: JString,
+    menu_on_activate: jboolean,
 ) {
     #[cfg(target_os = "linux")]
     {
@@ -178,30 +296,38 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setTrayLinux(
             .with_env(|env| -> jni::errors::Result<()> {
                 use jni::objects::ReleaseMode;
 
-                use crate::tray::{PanoTrayData, update_tray};
+                use crate::tray::{PanoTrayData, TrayIconColor, update_tray};
 
                 let tooltip: String = tooltip.mutf8_chars(env)?.into();
 
-                let png_bytes_rust =
-                    unsafe { png_bytes.get_elements(env, ReleaseMode::NoCopyBack) }?.to_vec();
+                let icon_bytes_rust =
+                    unsafe { icon_bytes.get_elements(env, ReleaseMode::NoCopyBack) }?.to_vec();
                 // convert vec<i8> to vec<u8>
-                let png_bytes_rust = png_bytes_rust.into_iter().map(|b| b as u8).collect();
-
-                let len = menu_item_ids.len(env)?;
-                let mut menu_items = Vec::<(String, String)>::with_capacity(len);
-
-                for i in 0..len {
-                    let id = menu_item_ids.get_element(env, i)?.to_string();
-                    let text = menu_item_texts.get_element(env, i)?.to_string();
-
-                    menu_items.push((id, text));
-                }
+                let icon_bytes_rust = icon_bytes_rust.into_iter().map(|b| b as u8).collect();
+
+                let icon_name: String = icon_name.mutf8_chars(env)?.into();
+
+                let icon_color: String = icon_color.mutf8_chars(env)?.into();
+                let icon_color = match icon_color.as_str() {
+                    "invert" => TrayIconColor::Invert,
+                    "auto" => TrayIconColor::Auto,
+                    "accent" => TrayIconColor::Accent,
+                    _ => TrayIconColor::Original,
+                };
+
+                let menu_json: String = menu_json.mutf8_chars(env)?.into();
+                let menu = serde_json::from_str(&menu_json).unwrap_or_else(|e| {
+                    log::error!("Invalid tray menu: {e}");
+                    vec![]
+                });
 
                 update_tray(PanoTrayData {
                     tooltip,
-                    png_bytes: png_bytes_rust,
-                    invert,
-                    menu_items,
+                    icon_bytes: icon_bytes_rust,
+                    icon_name,
+                    icon_color,
+                    menu,
+                    menu_on_activate,
                 });
                 Ok(())
             })
@@ -209,6 +335,100 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setTrayLinux(
     }
 }
 
+// status is one of "active", "passive" or "needsAttention".
+// the icon names are from the icon theme and take precedence over the icon bytes, pass empty to unset
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setTrayStatusLinux(
+    mut unowned_env: EnvUnowned,
+    _class: JClass,
+    status: JString,
+    attention_icon_name: JString,
+    attention_icon_bytes: JByteArray,
+    overlay_icon_name: JString,
+    overlay_icon_bytes: JByteArray,
+) {
+    #[cfg(target_os = "linux")]
+    {
+        unowned_env
+            .with_env(|env| -> jni::errors::Result<()> {
+                use crate::tray::{PanoTrayStatus, update_tray_status};
+
+                let status: String = status.mutf8_chars(env)?.into();
+                let status = match status.as_str() {
+                    "passive" => ksni::Status::Passive,
+                    "needsAttention" => ksni::Status::NeedsAttention,
+                    _ => ksni::Status::Active,
+                };
+
+                update_tray_status(PanoTrayStatus {
+                    status,
+                    attention_icon_name: attention_icon_name.mutf8_chars(env)?.into(),
+                    attention_icon_bytes: env.convert_byte_array(&attention_icon_bytes)?,
+                    overlay_icon_name: overlay_icon_name.mutf8_chars(env)?.into(),
+                    overlay_icon_bytes: env.convert_byte_array(&overlay_icon_bytes)?,
+                });
+                Ok(())
+            })
+            .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+    }
+}
+
+// shows the now playing track in the tooltip, above the lines from setTrayLinux.
+// an empty string goes back to the plain tooltip
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setTrayTooltipLinux(
+    mut unowned_env: EnvUnowned,
+    _class: JClass,
+    config_json: JString,
+) -> jboolean {
+    #[cfg(target_os = "linux")]
+    {
+        unowned_env
+            .with_env(|env| -> jni::errors::Result<jboolean> {
+                let config_json: String = config_json.mutf8_chars(env)?.into();
+
+                if config_json.is_empty() {
+                    tray_tooltip::set_config(None);
+                    return Ok(true);
+                }
+
+                match serde_json::from_str(&config_json) {
+                    Ok(config) => {
+                        tray_tooltip::set_config(Some(config));
+                        Ok(true)
+                    }
+                    Err(e) => {
+                        log::error!("Invalid tray tooltip config {config_json}: {e}");
+                        Ok(false)
+                    }
+                }
+            })
+            .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+// false if there is no tray host running, or before the first setTrayLinux
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_isTrayAvailableLinux(
+    _env: EnvUnowned,
+    _class: JClass,
+) -> jboolean {
+    #[cfg(target_os = "linux")]
+    {
+        tray::is_available()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_getMachineId<'local>(
     mut unowned_env: EnvUnowned<'local>,
@@ -260,6 +480,56 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_sendIpcCommand
         .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setIpcProfileId(
+    mut unowned_env: EnvUnowned,
+    _class: JClass,
+    profile_id: JString,
+) {
+    unowned_env
+        .with_env(|env| -> jni::errors::Result<()> {
+            let profile_id: String = profile_id.mutf8_chars(env)?.into();
+            ipc::set_profile_id(&profile_id);
+            Ok(())
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setIpcFilesystemSocketLinux(
+    _env: EnvUnowned,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    #[cfg(target_os = "linux")]
+    {
+        ipc::set_use_filesystem_socket(enabled);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_listIpcInstances<'local>(
+    mut unowned_env: EnvUnowned<'local>,
+    _class: JClass<'local>,
+) -> JObjectArray<'local, JString<'local>> {
+    unowned_env
+        .with_env(
+            |env| -> jni::errors::Result<JObjectArray<'_, JString<'_>>> {
+                let instances = ipc::list_instances();
+                let profile_ids =
+                    JObjectArray::<JString>::new(env, instances.len(), JString::null())?;
+
+                for (i, profile_id) in instances.iter().enumerate() {
+                    let j_profile_id = JString::from_str(env, profile_id)?;
+                    profile_ids.set_element(env, i, j_profile_id)?;
+                }
+
+                Ok(profile_ids)
+            },
+        )
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_isFileLockedWindows(
     mut unowned_env: EnvUnowned,
@@ -386,14 +656,126 @@ fn call_java_fn(env: &mut jni::Env, event: &JniCallback) -> Option<bool> {
             )
         }
 
+        JniCallback::DeepLink(deep_link) => {
+            let url = JString::from_str(env, &deep_link.url).unwrap();
+            let path = JString::from_str(env, &deep_link.path).unwrap();
+            let param_keys =
+                JObjectArray::<JString>::new(env, deep_link.params.len(), JString::null()).unwrap();
+            let param_values =
+                JObjectArray::<JString>::new(env, deep_link.params.len(), JString::null()).unwrap();
+
+            for (i, (key, value)) in deep_link.params.iter().enumerate() {
+                let j_key = JString::from_str(env, key).unwrap();
+                param_keys.set_element(env, i, j_key).unwrap();
+
+                let j_value = JString::from_str(env, value).unwrap();
+                param_values.set_element(env, i, j_value).unwrap();
+            }
+
+            env.call_static_method(
+                class,
+                jni_str!("onDeepLink"),
+                jni_sig!(
+                    "(Ljava/lang/String;Ljava/lang/String;[Ljava/lang/String;[Ljava/lang/String;)V"
+                ),
+                &[
+                    (&url).into(),
+                    (&path).into(),
+                    (&param_keys).into(),
+                    (&param_values).into(),
+                ],
+            )
+        }
+
         #[cfg(target_os = "linux")]
-        JniCallback::TrayItemClicked(item_id) => {
+        JniCallback::TrayItemClicked(item_id, value) => {
             let item_id = JString::from_str(env, item_id).unwrap();
+            let value = JString::from_str(env, value).unwrap();
             env.call_static_method(
                 class,
                 jni_str!("onTrayMenuItemClicked"),
-                jni_sig!("(Ljava/lang/String;)V"),
-                &[(&item_id).into()],
+                jni_sig!("(Ljava/lang/String;Ljava/lang/String;)V"),
+                &[(&item_id).into(), (&value).into()],
+            )
+        }
+
+        #[cfg(target_os = "linux")]
+        JniCallback::TrayActivated(activation) => {
+            use crate::tray::TrayActivation;
+
+            // x and y are 0 for scrolls, delta is 0 for clicks
+            let (kind, x, y, delta) = match activation {
+                TrayActivation::Activate { x, y } => ("activate", *x, *y, 0),
+                TrayActivation::SecondaryActivate { x, y } => ("secondaryActivate", *x, *y, 0),
+                TrayActivation::Scroll {
+                    delta,
+                    orientation: ksni::Orientation::Vertical,
+                } => ("scroll", 0, 0, *delta),
+                TrayActivation::Scroll {
+                    delta,
+                    orientation: ksni::Orientation::Horizontal,
+                } => ("scrollHorizontal", 0, 0, *delta),
+            };
+            let kind = JString::from_str(env, kind).unwrap();
+
+            env.call_static_method(
+                class,
+                jni_str!("onTrayActivated"),
+                jni_sig!("(Ljava/lang/String;III)V"),
+                &[(&kind).into(), x.into(), y.into(), delta.into()],
+            )
+        }
+
+        #[cfg(target_os = "linux")]
+        JniCallback::TrayAvailabilityChanged(available) => env.call_static_method(
+            class,
+            jni_str!("onTrayAvailabilityChanged"),
+            jni_sig!("(Z)V"),
+            &[(*available).into()],
+        ),
+
+        #[cfg(target_os = "linux")]
+        JniCallback::NotificationAction(id, action) => {
+            let action = JString::from_str(env, action).unwrap();
+
+            env.call_static_method(
+                class,
+                jni_str!("onNotificationAction"),
+                jni_sig!("(ILjava/lang/String;)V"),
+                &[(*id as jint).into(), (&action).into()],
+            )
+        }
+
+        #[cfg(target_os = "linux")]
+        JniCallback::NotificationCapabilitiesChanged(capabilities) => {
+            let capability_names =
+                JObjectArray::<JString>::new(env, capabilities.capabilities.len(), JString::null())
+                    .unwrap();
+
+            for (i, capability) in capabilities.capabilities.iter().enumerate() {
+                let capability = JString::from_str(env, capability).unwrap();
+                capability_names.set_element(env, i, capability).unwrap();
+            }
+
+            let server_name = JString::from_str(env, &capabilities.server_name).unwrap();
+            let server_vendor = JString::from_str(env, &capabilities.server_vendor).unwrap();
+            let server_version = JString::from_str(env, &capabilities.server_version).unwrap();
+            let spec_version = JString::from_str(env, &capabilities.spec_version).unwrap();
+
+            env.call_static_method(
+                class,
+                jni_str!("onNotificationCapabilitiesChanged"),
+                jni_sig!(
+                    "(Z[Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V"
+                ),
+                &[
+                    capabilities.available.into(),
+                    (&capability_names).into(),
+                    (&server_name).into(),
+                    (&server_vendor).into(),
+                    (&server_version).into(),
+                    (&spec_version).into(),
+                ],
             )
         }
 
@@ -415,6 +797,24 @@ fn call_java_fn(env: &mut jni::Env, event: &JniCallback) -> Option<bool> {
             &[(*is_dark_mode).into()],
         ),
 
+        JniCallback::DiscordStatusChanged(status) => {
+            let user_id = JString::from_str(env, &status.user_id).unwrap();
+            let username = JString::from_str(env, &status.username).unwrap();
+            let client_name = JString::from_str(env, &status.client_name).unwrap();
+
+            env.call_static_method(
+                class,
+                jni_str!("onDiscordStatus"),
+                jni_sig!("(ZLjava/lang/String;Ljava/lang/String;Ljava/lang/String;)V"),
+                &[
+                    status.connected.into(),
+                    (&user_id).into(),
+                    (&username).into(),
+                    (&client_name).into(),
+                ],
+            )
+        }
+
         JniCallback::IsAppIdAllowed(app_id) => {
             let app_id_j = JString::from_str(env, app_id).unwrap();
             env.call_static_method(
@@ -463,51 +863,30 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_startListening
 pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_updateDiscordActivity(
     mut unowned_env: EnvUnowned,
     _class: JClass,
-    client_id: JString,
-    name: JString,
-    state: JString,
-    details: JString,
-    large_text: JString,
-    start_time: jlong,
-    end_time: jlong,
-    art_url: JString,
-    details_url: JString,
-    is_playing: jboolean,
-    status_line: jint,
-    button_text: JString,
-    button_url: JString,
+    activity_json: JString,
 ) -> jboolean {
     unowned_env
         .with_env(|env| -> jni::errors::Result<jboolean> {
-            let client_id: String = client_id.mutf8_chars(env)?.into();
-            let name: String = name.mutf8_chars(env)?.into();
-            let state: String = state.mutf8_chars(env)?.into();
-            let details: String = details.mutf8_chars(env)?.into();
-            let large_text: String = large_text.mutf8_chars(env)?.into();
-            let art_url: String = art_url.mutf8_chars(env)?.into();
-            let details_url: String = details_url.mutf8_chars(env)?.into();
-            let button_text: String = button_text.mutf8_chars(env)?.into();
-            let button_url: String = button_url.mutf8_chars(env)?.into();
-
-            let end_time = if end_time > 0 { Some(end_time) } else { None };
-
-            let activity = DiscordActivity {
-                client_id,
-                name,
-                state,
-                details,
-                large_text,
-                start_time,
-                end_time,
-                art_url,
-                details_url,
-                status_line,
-                is_playing,
-                button_text,
-                button_url,
+            let activity_json: String = activity_json.mutf8_chars(env)?.into();
+
+            let activity = match serde_json::from_str::<DiscordActivity>(&activity_json) {
+                Ok(activity) => activity,
+                Err(e) => {
+                    log::error!("Invalid Discord activity {activity_json}: {e}");
+                    return Ok(false);
+                }
             };
 
-            Ok(discord_rpc::update(activity).is_ok())
+            let jvm = env.get_java_vm()?;
+
+            discord_rpc::start_worker(move |status| {
+                let event = JniCallback::DiscordStatusChanged(status.clone());
+                let _ = jvm.attach_current_thread(|env| -> jni::errors::Result<Option<bool>> {
+                    Ok(call_java_fn(env, &event))
+                });
+            });
+
+            Ok(discord_rpc::update(activity))
         })
         .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
 }
@@ -518,7 +897,64 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_clearDiscordAc
     _class: JClass,
     shutdown: jboolean,
 ) -> jboolean {
-    discord_rpc::clear(shutdown).is_ok()
+    discord_rpc::clear(shutdown)
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_getDiscordStatus<'local>(
+    mut unowned_env: EnvUnowned<'local>,
+    _class: JClass<'local>,
+) -> JString<'local> {
+    unowned_env
+        .with_env(|env| -> jni::errors::Result<JString<'_>> {
+            let status = serde_json::to_string(&discord_rpc::status()).unwrap_or_default();
+            JString::from_str(env, status)
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setNowPlayingServer(
+    _env: EnvUnowned,
+    _class: JClass,
+    enabled: jboolean,
+    port: jint,
+) -> jboolean {
+    let Ok(port) = u16::try_from(port) else {
+        log::error!("Invalid port {port}");
+        return false;
+    };
+
+    now_playing_server::set_enabled(enabled, port)
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_setNowPlayingFileOutput(
+    mut unowned_env: EnvUnowned,
+    _class: JClass,
+    config_json: JString,
+) -> jboolean {
+    unowned_env
+        .with_env(|env| -> jni::errors::Result<jboolean> {
+            let config_json: String = config_json.mutf8_chars(env)?.into();
+
+            if config_json.is_empty() {
+                now_playing_file::set_config(None);
+                return Ok(true);
+            }
+
+            match serde_json::from_str(&config_json) {
+                Ok(config) => {
+                    now_playing_file::set_config(Some(config));
+                    Ok(true)
+                }
+                Err(e) => {
+                    log::error!("Invalid now playing file config {config_json}: {e}");
+                    Ok(false)
+                }
+            }
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
 }
 
 #[unsafe(no_mangle)]
@@ -571,6 +1007,33 @@ pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_autoStartLinux
     }
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_registerUrlSchemeLinux(
+    _env: EnvUnowned,
+    _class: JClass,
+    add: jboolean,
+) {
+    #[cfg(target_os = "linux")]
+    {
+        let event = IncomingEvent::RegisterUrlScheme(add);
+        send_incoming_event(event);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_forwardDeepLink(
+    mut unowned_env: EnvUnowned,
+    _class: JClass,
+    url: JString,
+) -> jboolean {
+    unowned_env
+        .with_env(|env| -> jni::errors::Result<jboolean> {
+            let url: String = url.mutf8_chars(env)?.into();
+            Ok(deep_link::forward(&url))
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>()
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_arn_scrobble_PanoNativeComponents_openUrl(
     mut unowned_env: EnvUnowned,
@@ -1,6 +1,7 @@
+use serde::{Deserialize, Serialize};
 use strum::EnumString;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MetadataInfo {
     pub title: String,
     pub artist: String,
@@ -12,7 +13,7 @@ pub struct MetadataInfo {
     pub track_url: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PlaybackInfo {
     pub state: PlaybackState,
     pub position: i64,
@@ -32,7 +33,7 @@ pub struct SessionInfo {
     pub app_name: String,
 }
 
-#[derive(EnumString, strum::Display, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(EnumString, strum::Display, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PlaybackState {
     None,
     Stopped,
@@ -42,6 +43,98 @@ pub enum PlaybackState {
     Other,
 }
 
+// deserialized from the json passed to notify
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationAction {
+    pub id: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationUrgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+// deserialized from the json passed to notify, only used on linux
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationOptions {
+    pub urgency: NotificationUrgency,
+    // like "x-pano.nowplaying", empty for none
+    pub category: String,
+    // kept out of the notification history
+    pub transient: bool,
+    // not closed when an action is invoked
+    pub resident: bool,
+    pub suppress_sound: bool,
+    // -1 leaves it to the notification server, 0 never expires
+    pub timeout_ms: i32,
+}
+
+impl Default for NotificationOptions {
+    fn default() -> Self {
+        Self {
+            urgency: NotificationUrgency::default(),
+            category: String::new(),
+            transient: false,
+            resident: false,
+            suppress_sound: false,
+            timeout_ms: 10000,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NotificationInfo {
+    // assigned by notify and passed back with every action.
+    // a notification with the id of one still shown replaces it
+    pub id: u32,
+    pub title: String,
+    pub body: String,
+    pub actions: Vec<NotificationAction>,
+    // the action sent when the notification itself is clicked, empty for none
+    pub default_action: String,
+    // album art or any other image, empty for none
+    pub image_bytes: Vec<u8>,
+    pub options: NotificationOptions,
+}
+
+// incoming events are debug logged, and the image can be hundreds of KB
+impl std::fmt::Debug for NotificationInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationInfo")
+            .field("id", &self.id)
+            .field("title", &self.title)
+            .field("body", &self.body)
+            .field("actions", &self.actions)
+            .field("default_action", &self.default_action)
+            .field(
+                "image_bytes",
+                &format_args!("{} bytes", self.image_bytes.len()),
+            )
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationCapabilities {
+    // false while no notification server is running
+    pub available: bool,
+    // as reported by the server, like "actions", "body-markup", "body-images" or "persistence"
+    pub capabilities: Vec<String>,
+    pub server_name: String,
+    pub server_vendor: String,
+    pub server_version: String,
+    pub spec_version: String,
+}
+
 #[derive(Debug, Clone)]
 pub enum IncomingEvent {
     Skip(String),
@@ -53,5 +146,8 @@ pub enum IncomingEvent {
     LaunchFilePicker(i32, i64, bool, String, String, Vec<String>),
     #[cfg(target_os = "linux")]
     AutoStart(bool),
-    Notification(String, String),
+    #[cfg(target_os = "linux")]
+    RegisterUrlScheme(bool),
+    Notification(NotificationInfo),
+    CloseNotification(u32),
 }
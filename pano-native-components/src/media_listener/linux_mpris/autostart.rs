@@ -5,7 +5,11 @@ use std::path::PathBuf;
 
 use ashpd::desktop::background::Background;
 
-fn get_exec_for_autostart() -> String {
+pub(super) fn get_exec_for_autostart() -> String {
+    if let Ok(appimage) = env::var("APPIMAGE") {
+        return appimage;
+    }
+
     let self_path = std::env::current_exe().and_then(fs::canonicalize).ok();
 
     let in_path = std::env::var("PATH").ok().and_then(|path_var| {
@@ -23,8 +27,8 @@ fn get_exec_for_autostart() -> String {
     }
 }
 
-pub fn autostart(add: bool) {
-    let desktop_file = env::var("XDG_CONFIG_HOME")
+pub(super) fn config_home() -> Option<PathBuf> {
+    env::var("XDG_CONFIG_HOME")
         .ok()
         .filter(|s| !s.is_empty())
         .map(PathBuf::from)
@@ -33,7 +37,10 @@ pub fn autostart(add: bool) {
                 .ok()
                 .map(|h| PathBuf::from(h).join(".config"))
         })
-        .map(|x| x.join("autostart/pano-scrobbler.desktop"));
+}
+
+pub fn autostart(add: bool) {
+    let desktop_file = config_home().map(|x| x.join("autostart/pano-scrobbler.desktop"));
 
     let desktop_file = match desktop_file {
         Some(path) => path,
@@ -44,11 +51,7 @@ pub fn autostart(add: bool) {
     };
 
     if add {
-        let exec_path = if let Ok(appimage) = env::var("APPIMAGE") {
-            appimage
-        } else {
-            get_exec_for_autostart()
-        };
+        let exec_path = get_exec_for_autostart();
 
         // Escape embedded double-quotes before wrapping in quotes per XDG spec
         let escaped = exec_path.replace('"', "\\\"");
@@ -3,7 +3,10 @@ mod media_player2;
 mod metadata;
 mod mpris_listener;
 mod notifications;
+mod notifier;
 mod player;
 mod player_info_structs;
+mod url_scheme;
 
 pub use mpris_listener::listener;
+pub use notifier::capabilities as notification_capabilities;
@@ -1,10 +1,6 @@
 use std::{collections::HashMap, str::FromStr, sync::OnceLock, time::Duration};
 
-use ashpd::desktop::{
-    Icon,
-    notification::{DisplayHint, Notification, NotificationProxy, Priority},
-    open_uri::{OpenFileOptions, OpenFileRequest, OpenURIProxy},
-};
+use ashpd::desktop::open_uri::{OpenFileOptions, OpenFileRequest, OpenURIProxy};
 use futures_util::{TryFutureExt, stream::StreamExt};
 use tokio::{
     sync::{
@@ -21,14 +17,13 @@ use zbus::{
 };
 
 use crate::{
-    INCOMING_PLAYER_EVENT_TX, file_picker, ipc,
+    INCOMING_PLAYER_EVENT_TX, deep_link, file_picker, ipc,
     jni_callback::JniCallback,
     media_events::{IncomingEvent, MetadataInfo, PlaybackInfo, PlaybackState, SessionInfo},
     media_listener::linux_mpris::{
-        autostart, media_player2::MediaPlayer2Proxy, notifications::NotificationsProxy,
-        player::PlayerProxy,
+        autostart, media_player2::MediaPlayer2Proxy, notifier, player::PlayerProxy, url_scheme,
     },
-    theme_observer,
+    notification_scheduler, now_playing, theme_observer,
 };
 use crate::{media_listener::linux_mpris::metadata::Metadata, tray};
 
@@ -78,6 +73,7 @@ pub async fn listener(
 
     let (outgoing_tx, mut outgoing_rx) = mpsc::channel(10);
     OUTGOING_PLAYER_EVENT_TX.set(outgoing_tx.clone()).unwrap();
+    notification_scheduler::set_callback_sender(outgoing_tx.clone());
 
     let names_to_handles: RwLock<HashMap<String, PlayerListenerHandle>> =
         RwLock::new(HashMap::new());
@@ -234,38 +230,23 @@ pub async fn listener(
                     }
                 }
 
-                IncomingEvent::Notification(title, body) => {
+                IncomingEvent::RegisterUrlScheme(add) => {
                     if ashpd::is_sandboxed() {
-                        let proxy = NotificationProxy::new().await;
-
-                        if let Ok(proxy) = proxy {
-                            let _ = proxy
-                                .add_notification(
-                                    "com.arn.scrobble",
-                                    Notification::new(title)
-                                        .body(body.as_str())
-                                        .priority(Priority::Normal)
-                                        .icon(Icon::with_names(["pano-scrobbler"]))
-                                        .display_hint([DisplayHint::Tray]),
-                                )
-                                .await;
-                        }
+                        log::error!("The url scheme is registered by the sandbox manifest");
                     } else {
-                        let proxy = NotificationsProxy::new(&connection).await;
-                        if let Ok(proxy) = proxy {
-                            let _ = proxy
-                                .notify(
-                                    "Pano Scrobbler",
-                                    0,
-                                    "pano-scrobbler",
-                                    title,
-                                    body,
-                                    &[],
-                                    HashMap::new(),
-                                    10000,
-                                )
-                                .await;
-                        }
+                        url_scheme::register_url_scheme(*add);
+                    }
+                }
+
+                IncomingEvent::Notification(notification) => {
+                    if let Err(e) = notifier::notify(&connection, notification).await {
+                        log::error!("Error showing notification: {e}");
+                    }
+                }
+
+                IncomingEvent::CloseNotification(id) => {
+                    if let Err(e) = notifier::close_notification(&connection, *id).await {
+                        log::error!("Error closing notification: {e}");
                     }
                 }
 
@@ -348,20 +329,24 @@ pub async fn listener(
 
     // other listeners
     let ipc_commands = ipc::commands_listener(move |command: String, arg: String| {
-        let event = JniCallback::IpcCallback(command, arg);
-        let _ = OUTGOING_PLAYER_EVENT_TX.get().unwrap().try_send(event);
+        if let Some(event) = deep_link::ipc_command_to_callback(command, arg) {
+            let _ = OUTGOING_PLAYER_EVENT_TX.get().unwrap().try_send(event);
+        }
     });
 
     let tray = tray::tray_listener(outgoing_tx.clone());
 
     let outgoing_events = async {
         while let Some(event) = outgoing_rx.recv().await {
+            now_playing::publish(&event);
             jni_callback(event);
         }
 
         Ok(())
     };
 
+    let notification_events = notifier::notification_events(&connection, outgoing_tx.clone());
+
     let theme_observer = theme_observer::observe(outgoing_tx);
 
     let exit_res = tokio::try_join!(
@@ -370,6 +355,7 @@ pub async fn listener(
         ipc_commands,
         tray,
         outgoing_events,
+        notification_events,
         theme_observer,
     );
 
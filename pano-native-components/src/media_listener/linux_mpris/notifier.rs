@@ -0,0 +1,454 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use ashpd::desktop::{
+    Icon,
+    notification::{Button, Category, DisplayHint, Notification, NotificationProxy, Priority},
+};
+use futures_util::StreamExt;
+use image::{GenericImageView, imageops::FilterType};
+use tokio::{sync::mpsc, time::timeout};
+use zbus::{Connection, fdo::DBusProxy, zvariant::Value};
+
+use crate::{
+    jni_callback::{CLOSED_ACTION, JniCallback},
+    media_events::{
+        NotificationCapabilities, NotificationInfo, NotificationOptions, NotificationUrgency,
+    },
+    media_listener::linux_mpris::notifications::NotificationsProxy,
+};
+
+// the action key that notification servers send when the notification itself is clicked
+const DEFAULT_ACTION_KEY: &str = "default";
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+
+// what every version of the portal accepts, whatever the desktop does with it
+const PORTAL_CAPABILITIES: [&str; 3] = ["actions", "body", "icon-static"];
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+static CAPABILITIES: LazyLock<Mutex<NotificationCapabilities>> =
+    LazyLock::new(|| Mutex::new(NotificationCapabilities::default()));
+
+struct SentNotification {
+    id: u32,
+    default_action: String,
+}
+
+// the notification server picks its own ids, these are mapped back to ours until closed.
+// the portal takes our ids as they are, so it needs no mapping
+static SENT: LazyLock<Mutex<HashMap<u32, SentNotification>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn server_id(id: u32) -> Option<u32> {
+    SENT.lock()
+        .unwrap()
+        .iter()
+        .find(|(_, notification)| notification.id == id)
+        .map(|(server_id, _)| *server_id)
+}
+
+// larger images are scaled down, as they are sent whole over dbus
+const MAX_IMAGE_SIZE: u32 = 256;
+
+// the image-data hint, an (iiibiiay) struct of raw rgba pixels
+fn image_data(bytes: &[u8]) -> Option<Value<'static>> {
+    let mut img = match image::load_from_memory(bytes) {
+        Ok(img) => img,
+        Err(e) => {
+            log::error!("Invalid notification image: {e}");
+            return None;
+        }
+    };
+
+    if img.width().max(img.height()) > MAX_IMAGE_SIZE {
+        img = img.resize(MAX_IMAGE_SIZE, MAX_IMAGE_SIZE, FilterType::Lanczos3);
+    }
+
+    let (width, height) = img.dimensions();
+    let data = img.to_rgba8().into_vec();
+
+    Some(Value::from((
+        width as i32,
+        height as i32,
+        (width * 4) as i32,
+        true,
+        8,
+        4,
+        data,
+    )))
+}
+
+fn hints(notification: &NotificationInfo) -> Vec<(&'static str, Value<'static>)> {
+    let NotificationOptions {
+        urgency,
+        category,
+        transient,
+        resident,
+        suppress_sound,
+        ..
+    } = &notification.options;
+
+    let urgency: u8 = match urgency {
+        NotificationUrgency::Low => 0,
+        NotificationUrgency::Normal => 1,
+        NotificationUrgency::Critical => 2,
+    };
+
+    let mut hints = vec![("urgency", Value::from(urgency))];
+
+    if !category.is_empty() {
+        hints.push(("category", Value::from(category.clone())));
+    }
+
+    // servers treat missing and false the same, so only set ones are sent
+    for (key, value) in [
+        ("transient", transient),
+        ("resident", resident),
+        ("suppress-sound", suppress_sound),
+    ] {
+        if *value {
+            hints.push((key, Value::from(true)));
+        }
+    }
+
+    if !notification.image_bytes.is_empty()
+        && let Some(image_data) = image_data(&notification.image_bytes)
+    {
+        hints.push(("image-data", image_data));
+    }
+
+    hints
+}
+
+pub async fn notify(
+    connection: &Connection,
+    notification: &NotificationInfo,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if ashpd::is_sandboxed() {
+        let options = &notification.options;
+
+        let priority = match options.urgency {
+            NotificationUrgency::Low => Priority::Low,
+            NotificationUrgency::Normal => Priority::Normal,
+            NotificationUrgency::Critical => Priority::Urgent,
+        };
+
+        let icon = if notification.image_bytes.is_empty() {
+            Icon::with_names(["pano-scrobbler"])
+        } else {
+            Icon::Bytes(notification.image_bytes.clone())
+        };
+
+        let mut display_hints = vec![DisplayHint::Tray];
+        if options.transient {
+            display_hints.push(DisplayHint::Transient);
+        }
+        if options.resident {
+            display_hints.push(DisplayHint::Persistent);
+        }
+
+        // the portal has no timeout, and ashpd can only set a sound file, not silence it
+        let mut portal_notification = Notification::new(&notification.title)
+            .body(notification.body.as_str())
+            .priority(priority)
+            .icon(icon)
+            .display_hint(display_hints);
+
+        if !options.category.is_empty() {
+            portal_notification =
+                portal_notification.category(Category::Other(options.category.clone()));
+        }
+
+        // without one, the portal activates the app on click
+        if !notification.default_action.is_empty() {
+            portal_notification =
+                portal_notification.default_action(notification.default_action.as_str());
+        }
+
+        for action in &notification.actions {
+            portal_notification =
+                portal_notification.button(Button::new(&action.label, &action.id));
+        }
+
+        // replaces any notification with the same id
+        NotificationProxy::new()
+            .await?
+            .add_notification(&notification.id.to_string(), portal_notification)
+            .await?;
+    } else {
+        // flattened pairs of action key and label
+        let mut actions = vec![];
+
+        if !notification.default_action.is_empty() {
+            actions.extend([DEFAULT_ACTION_KEY, notification.default_action.as_str()]);
+        }
+
+        for action in &notification.actions {
+            actions.extend([action.id.as_str(), action.label.as_str()]);
+        }
+
+        // 0 shows a new one, as does an id the server has already closed
+        let replaces_id = server_id(notification.id).unwrap_or(0);
+
+        let hints = hints(notification);
+
+        let server_id = NotificationsProxy::new(connection)
+            .await?
+            .notify(
+                "Pano Scrobbler",
+                replaces_id,
+                "pano-scrobbler",
+                &notification.title,
+                &notification.body,
+                &actions,
+                hints.iter().map(|(key, value)| (*key, value)).collect(),
+                notification.options.timeout_ms,
+            )
+            .await?;
+
+        SENT.lock().unwrap().insert(
+            server_id,
+            SentNotification {
+                id: notification.id,
+                default_action: notification.default_action.clone(),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn close_notification(
+    connection: &Connection,
+    id: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if ashpd::is_sandboxed() {
+        NotificationProxy::new()
+            .await?
+            .remove_notification(&id.to_string())
+            .await?;
+    } else if let Some(server_id) = server_id(id) {
+        // the server then signals NotificationClosed, which removes the mapping
+        NotificationsProxy::new(connection)
+            .await?
+            .close_notification(server_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn query_capabilities(proxy: &NotificationsProxy<'_>) -> NotificationCapabilities {
+    let query =
+        async { tokio::try_join!(proxy.get_capabilities(), proxy.get_server_information()) };
+
+    // a stuck server should not hold up the actions of the next one
+    match timeout(QUERY_TIMEOUT, query).await {
+        Ok(Ok((capabilities, (server_name, server_vendor, server_version, spec_version)))) => {
+            NotificationCapabilities {
+                available: true,
+                capabilities,
+                server_name,
+                server_vendor,
+                server_version,
+                spec_version,
+            }
+        }
+        Ok(Err(e)) => {
+            log::error!("Failed to query the notification server: {e}");
+            NotificationCapabilities::default()
+        }
+        Err(_) => {
+            log::error!("Timed out querying the notification server");
+            NotificationCapabilities::default()
+        }
+    }
+}
+
+async fn set_capabilities(
+    capabilities: NotificationCapabilities,
+    callback_sender: &mpsc::Sender<JniCallback>,
+) {
+    let changed = {
+        let mut current = CAPABILITIES.lock().unwrap();
+        let changed = *current != capabilities;
+        *current = capabilities.clone();
+        changed
+    };
+
+    if changed {
+        let _ = callback_sender
+            .send(JniCallback::NotificationCapabilitiesChanged(capabilities))
+            .await;
+    }
+}
+
+pub fn capabilities() -> NotificationCapabilities {
+    CAPABILITIES.lock().unwrap().clone()
+}
+
+pub async fn notification_events(
+    connection: &Connection,
+    callback_sender: mpsc::Sender<JniCallback>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if ashpd::is_sandboxed() {
+        let proxy = match NotificationProxy::new().await {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                log::error!("Failed to connect to Notification XDG portal: {e}");
+                return Ok(());
+            }
+        };
+
+        // the portal hides the server behind it and takes the same options everywhere
+        set_capabilities(
+            NotificationCapabilities {
+                available: true,
+                capabilities: PORTAL_CAPABILITIES.map(String::from).to_vec(),
+                server_name: "xdg-desktop-portal".to_string(),
+                spec_version: proxy.version().to_string(),
+                ..Default::default()
+            },
+            &callback_sender,
+        )
+        .await;
+
+        let mut actions = match proxy.receive_action_invoked().await {
+            Ok(actions) => actions,
+            Err(e) => {
+                log::error!("Failed to receive notification actions: {e}");
+                return Ok(());
+            }
+        };
+
+        // the portal has no signal for closed notifications
+        while let Some(action) = actions.next().await {
+            if let Ok(id) = action.id().parse() {
+                let _ = callback_sender
+                    .send(JniCallback::NotificationAction(
+                        id,
+                        action.name().to_string(),
+                    ))
+                    .await;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let signals = async {
+        let proxy = NotificationsProxy::new(connection).await?;
+        let action_invoked = proxy.receive_action_invoked().await?;
+        let notification_closed = proxy.receive_notification_closed().await?;
+        let dbus_proxy = DBusProxy::new(connection).await?;
+        let owner_changed = dbus_proxy
+            .receive_name_owner_changed_with_args(&[(0, NOTIFICATIONS_NAME)])
+            .await?;
+
+        Ok::<_, zbus::Error>((
+            proxy,
+            dbus_proxy,
+            action_invoked,
+            notification_closed,
+            owner_changed,
+        ))
+    };
+
+    // notifications still show without these, only their actions are lost
+    let (proxy, dbus_proxy, mut action_invoked, mut notification_closed, mut owner_changed) =
+        match signals.await {
+            Ok(signals) => signals,
+            Err(e) => {
+                log::error!("Failed to receive notification signals: {e}");
+                return Ok(());
+            }
+        };
+
+    // querying without an owner would start a server through dbus activation,
+    // so one that is not running yet is picked up by owner_changed instead
+    let has_owner = match NOTIFICATIONS_NAME.try_into() {
+        Ok(name) => dbus_proxy.name_has_owner(name).await.unwrap_or(false),
+        Err(_) => false,
+    };
+
+    if has_owner {
+        set_capabilities(query_capabilities(&proxy).await, &callback_sender).await;
+    }
+
+    loop {
+        tokio::select! {
+            Some(signal) = action_invoked.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+
+                // other apps' notifications are signalled to everyone
+                let callback = SENT.lock().unwrap().get(&args.id).map(|notification| {
+                    let action = if args.action_key == DEFAULT_ACTION_KEY {
+                        notification.default_action.clone()
+                    } else {
+                        args.action_key.to_string()
+                    };
+
+                    JniCallback::NotificationAction(notification.id, action)
+                });
+
+                if let Some(callback) = callback {
+                    let _ = callback_sender.send(callback).await;
+                }
+            }
+
+            Some(signal) = notification_closed.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let notification = SENT.lock().unwrap().remove(&args.id);
+
+                if let Some(notification) = notification {
+                    let _ = callback_sender
+                        .send(JniCallback::NotificationAction(
+                            notification.id,
+                            CLOSED_ACTION.to_string(),
+                        ))
+                        .await;
+                }
+            }
+
+            // the server was started, replaced or has quit
+            Some(signal) = owner_changed.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+
+                // the notifications of the old server are gone with it
+                let sent = SENT.lock().unwrap().drain().collect::<Vec<_>>();
+                for (_, notification) in sent {
+                    let _ = callback_sender
+                        .send(JniCallback::NotificationAction(
+                            notification.id,
+                            CLOSED_ACTION.to_string(),
+                        ))
+                        .await;
+                }
+
+                // querying without an owner would start a new server through dbus activation
+                let capabilities = if args.new_owner().is_some() {
+                    query_capabilities(&proxy).await
+                } else {
+                    NotificationCapabilities::default()
+                };
+
+                set_capabilities(capabilities, &callback_sender).await;
+            }
+
+            else => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::deep_link::SCHEME;
+use crate::media_listener::linux_mpris::autostart::{config_home, get_exec_for_autostart};
+
+const HANDLER_DESKTOP_FILE: &str = "pano-scrobbler-url-handler.desktop";
+const DEFAULT_APPLICATIONS_SECTION: &str = "[Default Applications]";
+
+fn data_home() -> Option<PathBuf> {
+    env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var("HOME")
+                .ok()
+                .map(|h| PathBuf::from(h).join(".local/share"))
+        })
+}
+
+// sets the key to the handler in the [Default Applications] section, or removes the handler from it,
+// keeping everything else as it is. handlers that the user picked for other desktop files are kept
+fn update_mimeapps(contents: &str, key: &str, handler: &str, add: bool) -> String {
+    let mut lines = Vec::new();
+    let mut in_section = false;
+    let mut section_found = false;
+    let mut written = !add;
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('[') {
+            // leaving the section without having seen the key
+            if in_section && !written {
+                lines.push(format!("{key}={handler}"));
+                written = true;
+            }
+
+            in_section = trimmed == DEFAULT_APPLICATIONS_SECTION;
+            section_found |= in_section;
+        } else if in_section
+            && let Some((k, v)) = trimmed.split_once('=')
+            && k.trim() == key
+        {
+            if add {
+                if !written {
+                    lines.push(format!("{key}={handler}"));
+                    written = true;
+                }
+                continue;
+            }
+
+            // the value is a list of desktop files, in order of preference
+            let handlers = v
+                .split(';')
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .collect::<Vec<_>>();
+
+            if handlers.contains(&handler) {
+                let others = handlers
+                    .into_iter()
+                    .filter(|h| *h != handler)
+                    .collect::<Vec<_>>();
+
+                if !others.is_empty() {
+                    let separator = if v.trim_end().ends_with(';') { ";" } else { "" };
+                    lines.push(format!("{key}={}{separator}", others.join(";")));
+                }
+                continue;
+            }
+        }
+
+        lines.push(line.to_string());
+    }
+
+    if !written {
+        if !section_found {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(DEFAULT_APPLICATIONS_SECTION.to_string());
+        }
+        lines.push(format!("{key}={handler}"));
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+fn write_mimeapps(mimeapps_file: &Path, add: bool) -> std::io::Result<()> {
+    let key = format!("x-scheme-handler/{SCHEME}");
+
+    let contents = match fs::read_to_string(mimeapps_file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if !add {
+                return Ok(());
+            }
+            String::new()
+        }
+        Err(e) => return Err(e),
+    };
+
+    let updated = update_mimeapps(&contents, &key, HANDLER_DESKTOP_FILE, add);
+    if updated == contents {
+        return Ok(());
+    }
+
+    if let Some(parent) = mimeapps_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(mimeapps_file, updated)
+}
+
+pub fn register_url_scheme(add: bool) {
+    let (Some(data_home), Some(config_home)) = (data_home(), config_home()) else {
+        log::error!("Could not determine the url handler file paths (HOME not set)");
+        return;
+    };
+
+    let applications_dir = data_home.join("applications");
+    let desktop_file = applications_dir.join(HANDLER_DESKTOP_FILE);
+
+    if add {
+        // Escape embedded double-quotes before wrapping in quotes per XDG spec
+        let escaped = get_exec_for_autostart().replace('"', "\\\"");
+        let exec_command = format!("\"{escaped}\" %u");
+
+        let contents = format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=Pano Scrobbler\n\
+             Terminal=false\n\
+             NoDisplay=true\n\
+             Exec={exec_command}\n\
+             Icon=pano-scrobbler\n\
+             StartupWMClass=pano-scrobbler\n\
+             MimeType=x-scheme-handler/{SCHEME};\n"
+        );
+
+        if let Err(e) = fs::create_dir_all(&applications_dir) {
+            log::error!("Failed to create applications directory: {e}");
+            return;
+        }
+
+        if let Err(e) = fs::write(&desktop_file, contents) {
+            log::error!("Failed to write url handler file: {e}");
+            return;
+        }
+    } else if let Err(e) = fs::remove_file(&desktop_file)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        log::error!("Failed to remove url handler file: {e}");
+    }
+
+    if let Err(e) = write_mimeapps(&config_home.join("mimeapps.list"), add) {
+        log::error!("Failed to update mimeapps.list: {e}");
+    }
+
+    // not all desktops read mimeapps.list directly, refresh the mime cache when the tool is available
+    if let Err(e) = std::process::Command::new("update-desktop-database")
+        .arg(&applications_dir)
+        .status()
+    {
+        log::debug!("Could not run update-desktop-database: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "x-scheme-handler/pano-scrobbler";
+
+    fn add(contents: &str) -> String {
+        update_mimeapps(contents, KEY, HANDLER_DESKTOP_FILE, true)
+    }
+
+    fn remove(contents: &str) -> String {
+        update_mimeapps(contents, KEY, HANDLER_DESKTOP_FILE, false)
+    }
+
+    #[test]
+    fn adds_missing_section() {
+        assert_eq!(
+            add("[Added Associations]\ntext/plain=gedit.desktop;\n"),
+            "[Added Associations]\n\
+             text/plain=gedit.desktop;\n\
+             \n\
+             [Default Applications]\n\
+             x-scheme-handler/pano-scrobbler=pano-scrobbler-url-handler.desktop\n"
+        );
+        assert_eq!(
+            add(""),
+            "[Default Applications]\n\
+             x-scheme-handler/pano-scrobbler=pano-scrobbler-url-handler.desktop\n"
+        );
+    }
+
+    #[test]
+    fn only_changes_default_applications() {
+        let contents = "[Added Associations]\n\
+                        x-scheme-handler/pano-scrobbler=other.desktop;\n\
+                        [Default Applications]\n\
+                        text/html=firefox.desktop\n\
+                        [Removed Associations]\n\
+                        text/plain=vim.desktop;\n";
+
+        assert_eq!(
+            add(contents),
+            "[Added Associations]\n\
+             x-scheme-handler/pano-scrobbler=other.desktop;\n\
+             [Default Applications]\n\
+             text/html=firefox.desktop\n\
+             x-scheme-handler/pano-scrobbler=pano-scrobbler-url-handler.desktop\n\
+             [Removed Associations]\n\
+             text/plain=vim.desktop;\n"
+        );
+        assert_eq!(remove(contents), contents);
+    }
+
+    #[test]
+    fn replaces_duplicate_keys() {
+        assert_eq!(
+            add("[Default Applications]\n\
+                 x-scheme-handler/pano-scrobbler=old.desktop\n\
+                 text/html=firefox.desktop\n\
+                 x-scheme-handler/pano-scrobbler=older.desktop\n"),
+            "[Default Applications]\n\
+             x-scheme-handler/pano-scrobbler=pano-scrobbler-url-handler.desktop\n\
+             text/html=firefox.desktop\n"
+        );
+    }
+
+    #[test]
+    fn removes_only_own_handler() {
+        assert_eq!(
+            remove(
+                "[Default Applications]\n\
+                    x-scheme-handler/pano-scrobbler=pano-scrobbler-url-handler.desktop\n\
+                    text/html=firefox.desktop\n"
+            ),
+            "[Default Applications]\n\
+             text/html=firefox.desktop\n"
+        );
+
+        // picked by the user for another install
+        let contents = "[Default Applications]\n\
+                        x-scheme-handler/pano-scrobbler=flatpak-pano.desktop\n";
+        assert_eq!(remove(contents), contents);
+
+        assert_eq!(
+            remove(
+                "[Default Applications]\n\
+                    x-scheme-handler/pano-scrobbler=pano-scrobbler-url-handler.desktop;other.desktop;\n"
+            ),
+            "[Default Applications]\n\
+             x-scheme-handler/pano-scrobbler=other.desktop;\n"
+        );
+    }
+}
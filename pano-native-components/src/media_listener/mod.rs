@@ -5,7 +5,7 @@ mod linux_mpris;
 mod windows_smtc;
 
 #[cfg(target_os = "linux")]
-pub use linux_mpris::listener;
+pub use linux_mpris::{listener, notification_capabilities};
 
 #[cfg(target_os = "windows")]
 pub use windows_smtc::listener;
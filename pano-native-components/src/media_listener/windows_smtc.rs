@@ -2,7 +2,7 @@ use crate::INCOMING_PLAYER_EVENT_TX;
 use crate::file_picker::launch_file_picker;
 use crate::jni_callback::JniCallback;
 use crate::media_events::{IncomingEvent, MetadataInfo, PlaybackInfo, PlaybackState, SessionInfo};
-use crate::{ipc, theme_observer};
+use crate::{deep_link, ipc, now_playing, theme_observer};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Instant;
@@ -117,12 +117,21 @@ pub async fn listener(
                     break;
                 }
 
-                IncomingEvent::Notification(title, body) => {
-                    if let Err(e) = show_notification(&title, &body) {
+                // toasts have no action buttons yet, clicking one still launches the app
+                IncomingEvent::Notification(notification) => {
+                    if let Err(e) =
+                        show_notification(notification.id, &notification.title, &notification.body)
+                    {
                         log::error!("Error showing notification: {e:?}");
                     }
                 }
 
+                IncomingEvent::CloseNotification(id) => {
+                    if let Err(e) = close_notification(id) {
+                        log::error!("Error closing notification: {e:?}");
+                    }
+                }
+
                 IncomingEvent::LaunchFilePicker(
                     request_id,
                     hwnd,
@@ -174,6 +183,7 @@ pub async fn listener(
 
     let outgoing_events = async {
         while let Some(event) = outgoing_rx.recv().await {
+            now_playing::publish(&event);
             jni_callback(event);
         }
 
@@ -182,8 +192,9 @@ pub async fn listener(
 
     // other listeners
     let ipc_commands = ipc::commands_listener(|command: String, arg: String| {
-        let event = JniCallback::IpcCallback(command, arg);
-        send_outgoing_event(event);
+        if let Some(event) = deep_link::ipc_command_to_callback(command, arg) {
+            send_outgoing_event(event);
+        }
     });
 
     let theme_observer_future = theme_observer::observe(outgoing_tx_clone);
@@ -713,9 +724,12 @@ impl SessionTracker {
     }
 }
 
-fn show_notification(title: &str, body: &str) -> windows::core::Result<()> {
-    const AUMID: &str = "com.arn.scrobble";
+const AUMID: &str = "com.arn.scrobble";
 
+// a toast replaces the one with the same tag and group
+const NOTIFICATION_GROUP: &str = "pano";
+
+fn show_notification(id: u32, title: &str, body: &str) -> windows::core::Result<()> {
     let template = r#"
         <toast activationType="protocol" launch="">
             <visual>
@@ -748,9 +762,19 @@ fn show_notification(title: &str, body: &str) -> windows::core::Result<()> {
 
     let toast = ToastNotification::CreateToastNotification(&toast_xml)?;
     toast.SetExpiresOnReboot(true)?;
+    toast.SetTag(&HSTRING::from(id.to_string()))?;
+    toast.SetGroup(&HSTRING::from(NOTIFICATION_GROUP))?;
 
     let notifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(AUMID))?;
     notifier.Show(&toast)?;
 
     Ok(())
 }
+
+fn close_notification(id: u32) -> windows::core::Result<()> {
+    ToastNotificationManager::History()?.RemoveGroupedTagWithId(
+        &HSTRING::from(id.to_string()),
+        &HSTRING::from(NOTIFICATION_GROUP),
+        &HSTRING::from(AUMID),
+    )
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+use tokio::{
+    sync::mpsc,
+    time::{Duration, Instant, sleep_until},
+};
+
+#[cfg(target_os = "linux")]
+use crate::jni_callback::{CLOSED_ACTION, JniCallback};
+use crate::{
+    media_events::{IncomingEvent, NotificationInfo},
+    send_incoming_event,
+};
+
+// deserialized from the json passed to setNotificationRateLimits
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationRateLimits {
+    // held notifications are let out once their category has had none for this long
+    pub quiet_period_ms: u64,
+    // keyed by NotificationOptions::category, other categories are never held
+    pub categories: HashMap<String, CategoryRateLimit>,
+}
+
+impl Default for NotificationRateLimits {
+    fn default() -> Self {
+        Self {
+            quiet_period_ms: 5000,
+            categories: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CategoryRateLimit {
+    // shown right away, any more within window_ms are held
+    pub max_per_window: usize,
+    pub window_ms: u64,
+    // shown instead of two or more held notifications, "{count}" is replaced with their number.
+    // e.g. "{count} scrobbles failed"
+    pub summary_title: String,
+    // the last held notification's title if empty
+    pub summary_body: String,
+}
+
+impl Default for CategoryRateLimit {
+    fn default() -> Self {
+        Self {
+            max_per_window: 3,
+            window_ms: 60000,
+            summary_title: "{count} notifications".to_string(),
+            summary_body: String::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SchedulerCommand {
+    Notify(Box<NotificationInfo>),
+    Close(u32),
+    SetRateLimits(NotificationRateLimits),
+}
+
+static COMMAND_TX: OnceLock<mpsc::UnboundedSender<SchedulerCommand>> = OnceLock::new();
+
+#[cfg(target_os = "linux")]
+static CALLBACK_TX: OnceLock<mpsc::Sender<JniCallback>> = OnceLock::new();
+
+fn send_command(command: SchedulerCommand) {
+    let tx = COMMAND_TX.get_or_init(|| {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        std::thread::spawn(move || {
+            if let Err(e) = worker(rx) {
+                log::error!("Notification scheduler stopped: {e}");
+            }
+        });
+
+        tx
+    });
+
+    let _ = tx.send(command);
+}
+
+/// Shows the notification now, or holds it if its category is over the rate limit.
+pub fn notify(notification: NotificationInfo) {
+    send_command(SchedulerCommand::Notify(Box::new(notification)));
+}
+
+/// Closes the notification, or drops it if it is still held.
+pub fn close(id: u32) {
+    send_command(SchedulerCommand::Close(id));
+}
+
+pub fn set_rate_limits(rate_limits: NotificationRateLimits) {
+    send_command(SchedulerCommand::SetRateLimits(rate_limits));
+}
+
+/// Set by the listener, so that java hears about held notifications that are never shown.
+#[cfg(target_os = "linux")]
+pub fn set_callback_sender(callback_sender: mpsc::Sender<JniCallback>) {
+    let _ = CALLBACK_TX.set(callback_sender);
+}
+
+// the same as the notifier sends for a shown one that goes away,
+// so java can forget the id either way
+async fn send_closed(id: u32) {
+    #[cfg(target_os = "linux")]
+    if let Some(callback_sender) = CALLBACK_TX.get() {
+        let _ = callback_sender
+            .send(JniCallback::NotificationAction(
+                id,
+                CLOSED_ACTION.to_string(),
+            ))
+            .await;
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = id;
+}
+
+#[derive(Default)]
+struct CategoryState {
+    // times within the window that notifications of this category were shown
+    shown_at: VecDeque<Instant>,
+    held: Vec<NotificationInfo>,
+    // when the held notifications go out, pushed back by every new one
+    flush_at: Option<Instant>,
+}
+
+struct Scheduler {
+    rate_limits: NotificationRateLimits,
+    categories: HashMap<String, CategoryState>,
+}
+
+impl Scheduler {
+    fn notify(&mut self, notification: NotificationInfo) {
+        let Some(rate_limit) = self
+            .rate_limits
+            .categories
+            .get(&notification.options.category)
+        else {
+            send_incoming_event(IncomingEvent::Notification(notification));
+            return;
+        };
+
+        let window = Duration::from_millis(rate_limit.window_ms);
+        let state = self
+            .categories
+            .entry(notification.options.category.clone())
+            .or_default();
+        let now = Instant::now();
+
+        while state
+            .shown_at
+            .front()
+            .is_some_and(|shown_at| now.duration_since(*shown_at) >= window)
+        {
+            state.shown_at.pop_front();
+        }
+
+        // once some are held, the rest of the burst joins them, so the order is kept
+        if state.held.is_empty() && state.shown_at.len() < rate_limit.max_per_window {
+            state.shown_at.push_back(now);
+            send_incoming_event(IncomingEvent::Notification(notification));
+        } else {
+            state.held.push(notification);
+            state.flush_at = Some(now + Duration::from_millis(self.rate_limits.quiet_period_ms));
+        }
+    }
+
+    async fn close(&mut self, id: u32) {
+        for state in self.categories.values_mut() {
+            if let Some(index) = state.held.iter().position(|n| n.id == id) {
+                state.held.remove(index);
+                if state.held.is_empty() {
+                    state.flush_at = None;
+                }
+                send_closed(id).await;
+                return;
+            }
+        }
+
+        send_incoming_event(IncomingEvent::CloseNotification(id));
+    }
+
+    fn next_flush(&self) -> Option<Instant> {
+        self.categories
+            .values()
+            .filter_map(|state| state.flush_at)
+            .min()
+    }
+
+    // a single held notification is shown as it is, more are summarized into the last one,
+    // which keeps its id. the ids of the rest are closed
+    async fn flush(&mut self, force: bool) {
+        let now = Instant::now();
+
+        for (category, state) in &mut self.categories {
+            if !force && state.flush_at.is_none_or(|flush_at| flush_at > now) {
+                continue;
+            }
+
+            state.flush_at = None;
+            let count = state.held.len();
+
+            let Some(mut notification) = state.held.pop() else {
+                continue;
+            };
+
+            if count > 1 {
+                let rate_limit = self
+                    .rate_limits
+                    .categories
+                    .get(category)
+                    .cloned()
+                    .unwrap_or_default();
+
+                let body = if rate_limit.summary_body.is_empty() {
+                    notification.title.clone()
+                } else {
+                    rate_limit.summary_body
+                };
+
+                notification.title = rate_limit
+                    .summary_title
+                    .replace("{count}", &count.to_string());
+                notification.body = body;
+                // the buttons and the click action were for one of them
+                notification.actions.clear();
+                notification.default_action.clear();
+                notification.image_bytes.clear();
+            }
+
+            for merged in state.held.drain(..) {
+                send_closed(merged.id).await;
+            }
+
+            state.shown_at.push_back(now);
+            send_incoming_event(IncomingEvent::Notification(notification));
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn worker(
+    mut rx: mpsc::UnboundedReceiver<SchedulerCommand>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut scheduler = Scheduler {
+        rate_limits: NotificationRateLimits::default(),
+        categories: HashMap::new(),
+    };
+
+    loop {
+        let flush_at = scheduler.next_flush();
+
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(SchedulerCommand::Notify(notification)) => scheduler.notify(*notification),
+                Some(SchedulerCommand::Close(id)) => scheduler.close(id).await,
+                Some(SchedulerCommand::SetRateLimits(rate_limits)) => {
+                    // nothing held under the old limits is lost
+                    scheduler.flush(true).await;
+                    scheduler.rate_limits = rate_limits;
+                }
+                None => break,
+            },
+
+            _ = async {
+                match flush_at {
+                    Some(flush_at) => sleep_until(flush_at).await,
+                    None => std::future::pending().await,
+                }
+            } => scheduler.flush(false).await,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{
+    jni_callback::JniCallback,
+    media_events::{MetadataInfo, PlaybackInfo, PlaybackState},
+};
+
+// mirrors the MetadataChanged and PlaybackStateChanged callbacks, for consumers outside the JVM
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NowPlayingEvent {
+    Metadata {
+        app_id: String,
+        #[serde(flatten)]
+        metadata: MetadataInfo,
+    },
+    Playback {
+        app_id: String,
+        #[serde(flatten)]
+        playback: PlaybackInfo,
+    },
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SessionState {
+    pub metadata: Option<MetadataInfo>,
+    pub playback: Option<PlaybackInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NowPlaying {
+    pub app_id: String,
+    #[serde(flatten)]
+    pub session: SessionState,
+}
+
+#[derive(Debug, Default)]
+struct NowPlayingState {
+    sessions: HashMap<String, SessionState>,
+    // the session that last started playing, or the first one seen
+    current_app_id: Option<String>,
+}
+
+static EVENTS_TX: LazyLock<broadcast::Sender<NowPlayingEvent>> =
+    LazyLock::new(|| broadcast::channel(16).0);
+
+static STATE: LazyLock<Mutex<NowPlayingState>> =
+    LazyLock::new(|| Mutex::new(NowPlayingState::default()));
+
+// art of the current track, read once per track
+static ALBUM_ART: Mutex<Option<Arc<AlbumArt>>> = Mutex::new(None);
+
+const MAX_ALBUM_ART_SIZE: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct AlbumArt {
+    pub url: String,
+    pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+}
+
+pub fn subscribe() -> broadcast::Receiver<NowPlayingEvent> {
+    EVENTS_TX.subscribe()
+}
+
+pub fn current() -> Option<NowPlaying> {
+    let state = STATE.lock().unwrap();
+    let app_id = state.current_app_id.as_ref()?;
+
+    state.sessions.get(app_id).map(|session| NowPlaying {
+        app_id: app_id.clone(),
+        session: session.clone(),
+    })
+}
+
+// None for anything that is not a known image format
+fn image_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    let mime_type = match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => "image/webp",
+        _ => return None,
+    };
+
+    Some(mime_type)
+}
+
+fn read_album_art(art_url: &str) -> Option<AlbumArt> {
+    let path = url::Url::parse(art_url).ok()?.to_file_path().ok()?;
+
+    if std::fs::metadata(&path).ok()?.len() > MAX_ALBUM_ART_SIZE {
+        log::debug!("Album art too large: {}", path.display());
+        return None;
+    }
+
+    match std::fs::read(&path) {
+        // the player can point the art url at any file
+        Ok(bytes) => match image_mime_type(&bytes) {
+            Some(mime_type) => Some(AlbumArt {
+                url: art_url.to_string(),
+                mime_type,
+                bytes,
+            }),
+            None => {
+                log::debug!("Album art is not an image: {}", path.display());
+                None
+            }
+        },
+        Err(e) => {
+            log::debug!("Failed to read album art {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Returns the current track's album art, if the player provided it as a local file.
+/// Remote art urls are left for the consumer to fetch.
+pub fn album_art() -> Option<Arc<AlbumArt>> {
+    let art_url = current()?.session.metadata?.art_url;
+
+    if !art_url.starts_with("file://") {
+        return None;
+    }
+
+    let mut cached = ALBUM_ART.lock().unwrap();
+
+    if let Some(album_art) = cached.as_ref()
+        && album_art.url == art_url
+    {
+        return Some(album_art.clone());
+    }
+
+    let album_art = read_album_art(&art_url).map(Arc::new);
+    cached.clone_from(&album_art);
+    album_art
+}
+
+/// Returns the events needed to rebuild the current session's state from scratch.
+pub fn snapshot() -> Vec<NowPlayingEvent> {
+    let Some(NowPlaying { app_id, session }) = current() else {
+        return vec![];
+    };
+
+    let mut events = vec![];
+
+    if let Some(metadata) = session.metadata {
+        events.push(NowPlayingEvent::Metadata {
+            app_id: app_id.clone(),
+            metadata,
+        });
+    }
+
+    if let Some(playback) = session.playback {
+        events.push(NowPlayingEvent::Playback { app_id, playback });
+    }
+
+    events
+}
+
+// called by the listeners for every outgoing event, before it goes to the JVM
+pub fn publish(event: &JniCallback) {
+    let mut state = STATE.lock().unwrap();
+
+    let now_playing_event = match event {
+        JniCallback::SessionsChanged(session_infos) => {
+            state
+                .sessions
+                .retain(|app_id, _| session_infos.iter().any(|s| s.app_id == *app_id));
+
+            if let Some(app_id) = &state.current_app_id
+                && !state.sessions.contains_key(app_id)
+            {
+                state.current_app_id = None;
+            }
+
+            return;
+        }
+
+        JniCallback::MetadataChanged(app_id, metadata) => {
+            state.sessions.entry(app_id.clone()).or_default().metadata = Some(metadata.clone());
+
+            if state.current_app_id.is_none() {
+                state.current_app_id = Some(app_id.clone());
+            }
+
+            NowPlayingEvent::Metadata {
+                app_id: app_id.clone(),
+                metadata: metadata.clone(),
+            }
+        }
+
+        JniCallback::PlaybackStateChanged(app_id, playback) => {
+            state.sessions.entry(app_id.clone()).or_default().playback = Some(playback.clone());
+
+            if state.current_app_id.is_none() || playback.state == PlaybackState::Playing {
+                state.current_app_id = Some(app_id.clone());
+            }
+
+            NowPlayingEvent::Playback {
+                app_id: app_id.clone(),
+                playback: playback.clone(),
+            }
+        }
+
+        _ => return,
+    };
+
+    // only errors when there are no subscribers
+    let _ = EVENTS_TX.send(now_playing_event);
+}
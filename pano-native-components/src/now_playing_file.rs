@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+use tokio::sync::{broadcast, watch};
+
+use crate::{
+    media_events::{MetadataInfo, PlaybackState},
+    now_playing::{self, NowPlaying},
+};
+
+// deserialized from the json passed to setNowPlayingFileOutput
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NowPlayingFileConfig {
+    // the template is written here, e.g. "{artist} - {title} [{album}]"
+    pub file: Option<PathBuf>,
+    pub template: String,
+    // written instead of the template when paused or stopped, can also have placeholders.
+    // the template is kept while paused if paused_text is not set, set it to "" to clear the file
+    pub paused_text: Option<String>,
+    pub stopped_text: String,
+    // if set, each field is also written to its own file in this directory, e.g. title.txt
+    pub fields_dir: Option<PathBuf>,
+    // if set, the album art is copied here
+    pub cover_file: Option<PathBuf>,
+}
+
+impl Default for NowPlayingFileConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            template: "{artist} - {title}".to_string(),
+            paused_text: None,
+            stopped_text: String::new(),
+            fields_dir: None,
+            cover_file: None,
+        }
+    }
+}
+
+const FIELDS: [&str; 7] = [
+    "title",
+    "artist",
+    "album",
+    "album_artist",
+    "track_number",
+    "duration",
+    "app_id",
+];
+
+static CONFIG_TX: OnceLock<watch::Sender<Option<NowPlayingFileConfig>>> = OnceLock::new();
+
+/// Pass None to stop writing. The files are left as they are.
+pub fn set_config(config: Option<NowPlayingFileConfig>) {
+    let tx = CONFIG_TX.get_or_init(|| {
+        let (tx, rx) = watch::channel(None);
+
+        std::thread::spawn(move || {
+            if let Err(e) = writer(rx) {
+                log::error!("Now playing file writer stopped: {e}");
+            }
+        });
+
+        tx
+    });
+
+    tx.send_replace(config);
+}
+
+fn format_duration(duration_ms: i64) -> String {
+    if duration_ms <= 0 {
+        return String::new();
+    }
+
+    let secs = duration_ms / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn field_value(field: &str, app_id: &str, metadata: &MetadataInfo) -> String {
+    match field {
+        "title" => metadata.title.clone(),
+        "artist" => metadata.artist.clone(),
+        "album" => metadata.album.clone(),
+        "album_artist" => metadata.album_artist.clone(),
+        "track_number" if metadata.track_number > 0 => metadata.track_number.to_string(),
+        "duration" => format_duration(metadata.duration),
+        "app_id" => app_id.to_string(),
+        _ => String::new(),
+    }
+}
+
+// unknown placeholders are left as they are
+fn apply_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+
+    for (field, value) in values {
+        text = text.replace(&format!("{{{field}}}"), value);
+    }
+
+    text
+}
+
+// writes to a temporary file next to it and renames it, so readers never see a partial file
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+#[derive(Default)]
+struct Writer {
+    // skips rewriting files that have not changed
+    written: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl Writer {
+    fn write(&mut self, path: &Path, contents: Vec<u8>) {
+        if self.written.get(path) == Some(&contents) {
+            return;
+        }
+
+        match write_atomically(path, &contents) {
+            Ok(_) => {
+                self.written.insert(path.to_path_buf(), contents);
+            }
+            Err(e) => log::error!("Failed to write {}: {e}", path.display()),
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Err(e) = fs::remove_file(path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::error!("Failed to remove {}: {e}", path.display());
+        }
+
+        self.written.remove(path);
+    }
+
+    fn update(&mut self, config: &NowPlayingFileConfig, now_playing: Option<NowPlaying>) {
+        let (app_id, metadata, state) = match now_playing {
+            Some(NowPlaying { app_id, session }) => (
+                app_id,
+                session.metadata,
+                session.playback.map(|playback| playback.state),
+            ),
+            None => (String::new(), None, None),
+        };
+
+        let stopped = metadata.is_none()
+            || matches!(
+                state,
+                None | Some(PlaybackState::Stopped | PlaybackState::None)
+            );
+
+        // all fields are empty when stopped
+        let values = FIELDS
+            .iter()
+            .map(|field| {
+                let value = metadata
+                    .as_ref()
+                    .filter(|_| !stopped)
+                    .map(|metadata| field_value(field, &app_id, metadata))
+                    .unwrap_or_default();
+                (*field, value)
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(file) = &config.file {
+            let template = if stopped {
+                &config.stopped_text
+            } else if state == Some(PlaybackState::Paused) {
+                config.paused_text.as_ref().unwrap_or(&config.template)
+            } else {
+                &config.template
+            };
+
+            self.write(file, apply_template(template, &values).into_bytes());
+        }
+
+        if let Some(fields_dir) = &config.fields_dir {
+            for (field, value) in &values {
+                self.write(
+                    &fields_dir.join(format!("{field}.txt")),
+                    value.clone().into_bytes(),
+                );
+            }
+        }
+
+        if let Some(cover_file) = &config.cover_file {
+            match now_playing::album_art().filter(|_| !stopped) {
+                Some(album_art) => self.write(cover_file, album_art.bytes.clone()),
+                None => self.remove(cover_file),
+            }
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn writer(
+    mut config_rx: watch::Receiver<Option<NowPlayingFileConfig>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = Writer::default();
+    let mut events = now_playing::subscribe();
+
+    loop {
+        let config = config_rx.borrow_and_update().clone();
+
+        if let Some(config) = config {
+            writer.update(&config, now_playing::current());
+        }
+
+        tokio::select! {
+            changed = config_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                // a new config may point to new files
+                writer.written.clear();
+            }
+
+            event = events.recv() => match event {
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+
+    Ok(())
+}
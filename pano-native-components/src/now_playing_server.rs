@@ -0,0 +1,471 @@
+use std::{
+    error::Error,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Mutex,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, watch},
+};
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{
+        Message,
+        handshake::derive_accept_key,
+        protocol::{Role, WebSocketConfig},
+    },
+};
+
+use crate::now_playing::{self, NowPlayingEvent};
+
+// a small local server for stream overlays, e.g. an OBS browser source:
+//   GET /             a minimal overlay page
+//   GET /now-playing  the current track as json
+//   GET /art          the current album art
+//   GET /ws           websocket, sends the same events as the ipc subscribe command
+
+const MAX_REQUEST_HEAD_SIZE: usize = 8 * 1024;
+const MAX_REQUEST_HEADERS: usize = 64;
+// clients only send pings and closes, nothing they send is read
+const MAX_WS_CLIENT_MESSAGE_SIZE: usize = 64 * 1024;
+
+const OVERLAY_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>
+  body { margin: 0; font-family: sans-serif; color: white; text-shadow: 0 0 4px black; }
+  #np { display: flex; align-items: center; gap: 12px; padding: 12px; }
+  #np.hidden { display: none; }
+  #np.paused { opacity: 0.5; }
+  #art { width: 96px; height: 96px; object-fit: cover; border-radius: 8px; }
+  #title { font-size: 24px; font-weight: bold; }
+  #artist { font-size: 18px; }
+</style>
+</head>
+<body>
+<div id="np" class="hidden">
+  <img id="art" onerror="this.style.visibility='hidden'" onload="this.style.visibility='visible'">
+  <div><div id="title"></div><div id="artist"></div></div>
+</div>
+<script>
+  const np = document.getElementById("np");
+  function connect() {
+    const ws = new WebSocket(`ws://${location.host}/ws`);
+    ws.onmessage = (message) => {
+      const e = JSON.parse(message.data);
+      if (e.event === "metadata") {
+        document.getElementById("title").textContent = e.title;
+        document.getElementById("artist").textContent = e.artist;
+        document.getElementById("art").src = `/art?t=${Date.now()}`;
+        np.classList.remove("hidden");
+      } else if (e.event === "playback") {
+        np.classList.toggle("paused", e.state !== "Playing");
+        np.classList.toggle("hidden", e.state === "Stopped" || e.state === "None");
+      }
+    };
+    ws.onclose = () => setTimeout(connect, 2000);
+  }
+  connect();
+</script>
+</body>
+</html>
+"#;
+
+// the port of the running server and its shutdown signal
+static RUNNING: Mutex<Option<(u16, watch::Sender<bool>)>> = Mutex::new(None);
+
+struct Request {
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Starts or stops the server. Returns false if it could not listen on the port.
+pub fn set_enabled(enabled: bool, port: u16) -> bool {
+    let mut running = RUNNING.lock().unwrap();
+
+    if enabled
+        && let Some((running_port, _)) = running.as_ref()
+        && *running_port == port
+    {
+        return true;
+    }
+
+    if let Some((_, shutdown_tx)) = running.take() {
+        let _ = shutdown_tx.send(true);
+    }
+
+    if !enabled {
+        return true;
+    }
+
+    // bound here so that the caller knows if the port is taken
+    let listener = match std::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+    {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Failed to start the now playing server on port {port}: {e}");
+            return false;
+        }
+    };
+
+    let (tx, rx) = watch::channel(false);
+    *running = Some((port, tx));
+
+    std::thread::spawn(move || {
+        if let Err(e) = serve(listener, rx) {
+            log::error!("Now playing server stopped: {e}");
+        }
+    });
+
+    true
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn serve(
+    listener: std::net::TcpListener,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::from_std(listener)?;
+    let port = listener.local_addr()?.port();
+
+    loop {
+        tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok((conn, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(conn, port).await {
+                            log::debug!("Now playing server connection error: {e}");
+                        }
+                    });
+                }
+                Err(e) => log::error!("Now playing server accept error: {e}"),
+            },
+
+            _ = shutdown_rx.changed() => break,
+        }
+    }
+
+    Ok(())
+}
+
+async fn read_request(conn: &mut TcpStream) -> Result<Option<Request>, Box<dyn Error>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    loop {
+        let n = conn.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+
+        head.extend_from_slice(&buf[..n]);
+
+        if let Some(request) = parse_request(&head)? {
+            return Ok(Some(request));
+        }
+
+        if head.len() > MAX_REQUEST_HEAD_SIZE {
+            return Err("request head too large".into());
+        }
+    }
+}
+
+// None until the whole head has arrived. anything but GET gets an empty path
+fn parse_request(head: &[u8]) -> Result<Option<Request>, httparse::Error> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_REQUEST_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+
+    if request.parse(head)?.is_partial() {
+        return Ok(None);
+    }
+
+    if request.method != Some("GET") {
+        return Ok(Some(Request {
+            path: String::new(),
+            headers: vec![],
+        }));
+    }
+
+    let path = request
+        .path
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let headers = request
+        .headers
+        .iter()
+        .map(|header| {
+            (
+                header.name.to_string(),
+                String::from_utf8_lossy(header.value).trim().to_string(),
+            )
+        })
+        .collect();
+
+    Ok(Some(Request { path, headers }))
+}
+
+async fn write_response(
+    conn: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    extra_headers: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\
+         {extra_headers}\r\n",
+        body.len()
+    );
+
+    conn.write_all(head.as_bytes()).await?;
+    conn.write_all(body).await?;
+    conn.shutdown().await
+}
+
+const ALLOWED_HOSTS: [&str; 2] = ["127.0.0.1", "localhost"];
+
+// only answers to names that resolve to this machine, against dns rebinding.
+// pages on other origins still reach it by these names, but without cors headers
+// the browser does not let them read the responses
+fn is_host_allowed(request: &Request, port: u16) -> bool {
+    let Some(host) = request.header("Host") else {
+        return false;
+    };
+
+    ALLOWED_HOSTS
+        .iter()
+        .any(|name| host == *name || host == format!("{name}:{port}"))
+}
+
+// websockets are not covered by cors, so the origin is checked here.
+// clients outside a browser, like obs or scripts, send no origin
+fn is_origin_allowed(request: &Request, port: u16) -> bool {
+    let Some(origin) = request.header("Origin") else {
+        return true;
+    };
+
+    ALLOWED_HOSTS
+        .iter()
+        .any(|name| origin == format!("http://{name}:{port}"))
+}
+
+async fn handle_connection(mut conn: TcpStream, port: u16) -> Result<(), Box<dyn Error>> {
+    let Some(request) = read_request(&mut conn).await? else {
+        return Ok(());
+    };
+
+    if request.path.is_empty() {
+        write_response(&mut conn, "405 Method Not Allowed", "text/plain", "", b"").await?;
+        return Ok(());
+    }
+
+    if !is_host_allowed(&request, port) {
+        write_response(&mut conn, "403 Forbidden", "text/plain", "", b"").await?;
+        return Ok(());
+    }
+
+    match request.path.as_str() {
+        "/" => {
+            write_response(
+                &mut conn,
+                "200 OK",
+                "text/html; charset=utf-8",
+                "",
+                OVERLAY_HTML.as_bytes(),
+            )
+            .await?
+        }
+
+        "/now-playing" => {
+            let body = serde_json::to_vec(&now_playing::current())?;
+            write_response(&mut conn, "200 OK", "application/json", "", &body).await?
+        }
+
+        "/art" => {
+            if let Some(album_art) = now_playing::album_art() {
+                write_response(
+                    &mut conn,
+                    "200 OK",
+                    album_art.mime_type,
+                    "",
+                    &album_art.bytes,
+                )
+                .await?
+            } else if let Some(art_url) = now_playing::current()
+                .and_then(|now_playing| now_playing.session.metadata)
+                .map(|metadata| metadata.art_url)
+                .filter(|art_url| art_url.starts_with("https://") || art_url.starts_with("http://"))
+            {
+                let location = format!("Location: {art_url}\r\n");
+                write_response(&mut conn, "302 Found", "text/plain", &location, b"").await?
+            } else {
+                write_response(&mut conn, "404 Not Found", "text/plain", "", b"").await?
+            }
+        }
+
+        "/ws" if !is_origin_allowed(&request, port) => {
+            write_response(&mut conn, "403 Forbidden", "text/plain", "", b"").await?
+        }
+
+        "/ws" => match request.header("Sec-WebSocket-Key") {
+            Some(key) if request.header("Upgrade").is_some() => {
+                conn.write_all(switching_protocols_head(key).as_bytes())
+                    .await?;
+
+                handle_websocket(conn).await?
+            }
+            _ => write_response(&mut conn, "400 Bad Request", "text/plain", "", b"").await?,
+        },
+
+        _ => write_response(&mut conn, "404 Not Found", "text/plain", "", b"").await?,
+    }
+
+    Ok(())
+}
+
+fn switching_protocols_head(key: &str) -> String {
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    )
+}
+
+async fn handle_websocket(conn: impl AsyncRead + AsyncWrite + Unpin) -> Result<(), Box<dyn Error>> {
+    let config = WebSocketConfig::default()
+        .max_frame_size(Some(MAX_WS_CLIENT_MESSAGE_SIZE))
+        .max_message_size(Some(MAX_WS_CLIENT_MESSAGE_SIZE));
+    let mut ws = WebSocketStream::from_raw_socket(conn, Role::Server, Some(config)).await;
+
+    let mut events = now_playing::subscribe();
+
+    for event in now_playing::snapshot() {
+        write_ws_event(&mut ws, &event).await?;
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => write_ws_event(&mut ws, &event).await?,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+
+            // pings are answered and closes acknowledged while reading
+            message = ws.next() => match message {
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break,
+            },
+        }
+    }
+
+    Ok(())
+}
+
+async fn write_ws_event(
+    ws: &mut WebSocketStream<impl AsyncRead + AsyncWrite + Unpin>,
+    event: &NowPlayingEvent,
+) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string(event)?;
+    ws.send(Message::text(json)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_rfc_6455_handshake_key() {
+        assert!(
+            switching_protocols_head("dGhlIHNhbXBsZSBub25jZQ==")
+                .contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n")
+        );
+    }
+
+    #[test]
+    fn parses_get_requests() {
+        assert!(
+            parse_request(b"GET /ws HTTP/1.1\r\nHost: localhost")
+                .unwrap()
+                .is_none()
+        );
+
+        let request = parse_request(
+            b"GET /now-playing?t=1 HTTP/1.1\r\nHost: localhost:8080\r\norigin:  http://localhost:8080\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.path, "/now-playing");
+        assert_eq!(request.header("Origin"), Some("http://localhost:8080"));
+        assert!(is_host_allowed(&request, 8080));
+        assert!(!is_host_allowed(&request, 8081));
+        assert!(is_origin_allowed(&request, 8080));
+        assert!(!is_origin_allowed(&request, 8081));
+
+        let request = parse_request(b"POST / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert!(request.path.is_empty());
+
+        assert!(parse_request(b"GET / HTTP/1.1\r\nHost localhost\r\n\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_masked_and_extended_length_frames() {
+        let (server, client) = tokio::io::duplex(256 * 1024);
+
+        let (result, _) = tokio::join!(handle_websocket(server), async {
+            // clients mask every frame
+            let mut client = WebSocketStream::from_raw_socket(client, Role::Client, None).await;
+
+            // a 16 bit length, then a ping to see that it was read
+            client.send(Message::binary(vec![1; 300])).await.unwrap();
+            client.send(Message::Ping(vec![2; 4].into())).await.unwrap();
+
+            loop {
+                match client.next().await {
+                    Some(Ok(Message::Pong(payload))) => {
+                        assert_eq!(payload.as_ref(), [2; 4]);
+                        break;
+                    }
+                    Some(Ok(_)) => {}
+                    other => panic!("no pong: {other:?}"),
+                }
+            }
+
+            // a 64 bit length, over the limit
+            let _ = client
+                .send(Message::binary(vec![3; MAX_WS_CLIENT_MESSAGE_SIZE + 1]))
+                .await;
+
+            while let Some(Ok(_)) = client.next().await {}
+        });
+
+        assert!(result.is_ok());
+    }
+}
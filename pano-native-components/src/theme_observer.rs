@@ -7,9 +7,27 @@ pub async fn observe(
 ) -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(target_os = "linux")]
     {
-        use ashpd::desktop::settings::{ColorScheme, Settings};
+        use ashpd::desktop::{
+            Color,
+            settings::{ColorScheme, Settings},
+        };
         use futures_util::StreamExt;
 
+        use crate::tray::{self, TrayTheme};
+
+        enum ThemeChange {
+            ColorScheme(ColorScheme),
+            AccentColor(Color),
+        }
+
+        // out of range values mean that the desktop has no accent colour
+        fn accent_rgb(color: Color) -> Option<[u8; 3]> {
+            let rgb = [color.red(), color.green(), color.blue()];
+            rgb.iter()
+                .all(|c| (0.0..=1.0).contains(c))
+                .then(|| rgb.map(|c| (c * 255.0).round() as u8))
+        }
+
         let settings = match Settings::new().await {
             Ok(s) => s,
             Err(e) => {
@@ -26,15 +44,21 @@ pub async fn observe(
             }
         };
 
-        let is_dark_mode = match scheme {
+        let mut is_dark_mode = match scheme {
             ColorScheme::PreferDark => true,
             ColorScheme::PreferLight | ColorScheme::NoPreference => false,
         };
+        let mut accent = settings.accent_color().await.ok().and_then(accent_rgb);
+
+        tray::set_theme(TrayTheme {
+            dark: is_dark_mode,
+            accent,
+        });
         let _ = callback_sender
             .send(JniCallback::DarkModeChanged(is_dark_mode))
             .await;
 
-        let mut color_scheme_stream = match settings.receive_color_scheme_changed().await {
+        let color_scheme_stream = match settings.receive_color_scheme_changed().await {
             Ok(s) => s,
             Err(e) => {
                 log::error!("Failed to receive color scheme change stream: {}", e);
@@ -42,15 +66,35 @@ pub async fn observe(
             }
         };
 
-        while let Some(scheme) = color_scheme_stream.next().await {
-            let is_dark_mode = match scheme {
-                ColorScheme::PreferDark => true,
-                ColorScheme::PreferLight | ColorScheme::NoPreference => false,
-            };
+        // older portals do not have an accent colour
+        let accent_color_stream = settings.receive_accent_color_changed().await.ok();
+
+        let mut changes = std::pin::pin!(futures_util::stream::select(
+            color_scheme_stream.map(ThemeChange::ColorScheme),
+            futures_util::stream::iter(accent_color_stream)
+                .flatten()
+                .map(ThemeChange::AccentColor),
+        ));
+
+        while let Some(change) = changes.next().await {
+            match change {
+                ThemeChange::ColorScheme(scheme) => {
+                    is_dark_mode = match scheme {
+                        ColorScheme::PreferDark => true,
+                        ColorScheme::PreferLight | ColorScheme::NoPreference => false,
+                    };
+
+                    let _ = callback_sender
+                        .send(JniCallback::DarkModeChanged(is_dark_mode))
+                        .await;
+                }
+                ThemeChange::AccentColor(color) => accent = accent_rgb(color),
+            }
 
-            let _ = callback_sender
-                .send(JniCallback::DarkModeChanged(is_dark_mode))
-                .await;
+            tray::set_theme(TrayTheme {
+                dark: is_dark_mode,
+                accent,
+            });
         }
     }
 
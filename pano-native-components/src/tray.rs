@@ -1,80 +1,415 @@
-use crate::jni_callback::JniCallback;
-use image::GenericImageView;
-use ksni::{Icon, MenuItem, TrayMethods, menu::StandardItem};
-use std::{
-    hash::{DefaultHasher, Hash, Hasher},
-    sync::OnceLock,
+use crate::{
+    jni_callback::JniCallback,
+    tray_icon::{self, Recolor},
+    tray_tooltip::{self, NowPlayingTooltip},
+};
+use ksni::{
+    Icon, MenuItem, OfflineReason, Orientation, Status, TrayMethods,
+    menu::{CheckmarkItem, RadioGroup, RadioItem, StandardItem, SubMenu},
+};
+use serde::Deserialize;
+use std::sync::{
+    Arc, Mutex, OnceLock,
+    atomic::{AtomicBool, Ordering},
 };
 use tokio::sync::mpsc;
 
 pub struct PanoTrayData {
     pub tooltip: String,
-    pub png_bytes: Vec<u8>,
-    pub invert: bool,
-    pub menu_items: Vec<(String, String)>,
+    // png or svg
+    pub icon_bytes: Vec<u8>,
+    // a themed icon name, preferred by hosts over icon_bytes when set
+    pub icon_name: String,
+    pub icon_color: TrayIconColor,
+    pub menu: Vec<TrayMenuItem>,
+    // if false, a left click calls activate instead of opening the menu
+    pub menu_on_activate: bool,
 }
 
-struct PanoTray {
-    pub data: PanoTrayData,
-    pub prev_icon_hash: u64,
-    pub prev_icon: Option<Icon>,
+// changes independently of PanoTrayData, e.g. for a failed scrobble or paused playback
+#[derive(Clone)]
+pub struct PanoTrayStatus {
+    pub status: Status,
+    // shown while the status is NeedsAttention
+    pub attention_icon_name: String,
+    pub attention_icon_bytes: Vec<u8>,
+    // a badge drawn over the icon, such as a pause symbol
+    pub overlay_icon_name: String,
+    pub overlay_icon_bytes: Vec<u8>,
 }
 
-static TOKIO_USER_EVENT_SENDER: OnceLock<mpsc::Sender<PanoTrayData>> = OnceLock::new();
-static OUTGOING_TRAY_EVENT_TX: OnceLock<mpsc::Sender<JniCallback>> = OnceLock::new();
+impl Default for PanoTrayStatus {
+    fn default() -> Self {
+        Self {
+            status: Status::Active,
+            attention_icon_name: String::new(),
+            attention_icon_bytes: vec![],
+            overlay_icon_name: String::new(),
+            overlay_icon_bytes: vec![],
+        }
+    }
+}
 
-pub fn update_tray(pano_tray_data: PanoTrayData) {
-    if let Some(sender) = TOKIO_USER_EVENT_SENDER.get() {
-        sender.try_send(pano_tray_data).unwrap_or_else(|_| {
-            log::error!("Failed to send tray event");
-        });
+#[derive(Clone, Copy, PartialEq)]
+pub enum TrayIconColor {
+    Original,
+    Invert,
+    // recolours a monochrome icon to contrast with the color scheme
+    Auto,
+    // like Auto, but in the accent colour if the desktop has one
+    Accent,
+}
+
+#[derive(Clone, Copy)]
+pub struct TrayTheme {
+    pub dark: bool,
+    pub accent: Option<[u8; 3]>,
+}
+
+// breeze icon colours
+const DARK_THEME_ICON_COLOR: [u8; 3] = [0xfc, 0xfc, 0xfc];
+const LIGHT_THEME_ICON_COLOR: [u8; 3] = [0x23, 0x26, 0x29];
+
+enum TrayUpdate {
+    Data(PanoTrayData),
+    Status(PanoTrayStatus),
+    Theme(TrayTheme),
+    Tooltip(Option<NowPlayingTooltip>),
+}
+
+#[derive(Debug)]
+pub enum TrayActivation {
+    Activate {
+        x: i32,
+        y: i32,
+    },
+    SecondaryActivate {
+        x: i32,
+        y: i32,
+    },
+    Scroll {
+        delta: i32,
+        orientation: Orientation,
+    },
+}
+
+// deserialized from the json passed to setTrayLinux
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum TrayMenuItem {
+    Standard {
+        id: String,
+        label: String,
+        #[serde(default = "default_true")]
+        enabled: bool,
+        #[serde(default)]
+        icon_name: String,
+        // e.g. [["Control", "L"]]
+        #[serde(default)]
+        shortcut: Vec<Vec<String>>,
+    },
+    Separator,
+    Checkmark {
+        id: String,
+        label: String,
+        #[serde(default)]
+        checked: bool,
+        #[serde(default = "default_true")]
+        enabled: bool,
+        #[serde(default)]
+        icon_name: String,
+        #[serde(default)]
+        shortcut: Vec<Vec<String>>,
+    },
+    Radio {
+        id: String,
+        // id of the selected option
+        #[serde(default)]
+        selected: String,
+        options: Vec<TrayRadioOption>,
+    },
+    Submenu {
+        label: String,
+        #[serde(default = "default_true")]
+        enabled: bool,
+        #[serde(default)]
+        icon_name: String,
+        children: Vec<TrayMenuItem>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrayRadioOption {
+    pub id: String,
+    pub label: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub icon_name: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+struct TrayState {
+    data: PanoTrayData,
+    theme: TrayTheme,
+    icon: Option<Arc<Vec<Icon>>>,
+    status: PanoTrayStatus,
+    attention_icon: Option<Arc<Vec<Icon>>>,
+    overlay_icon: Option<Arc<Vec<Icon>>>,
+    now_playing_tooltip: Option<NowPlayingTooltip>,
+}
+
+impl TrayState {
+    fn new(data: PanoTrayData, status: PanoTrayStatus, theme: TrayTheme) -> Self {
+        let mut state = Self {
+            icon: tray_icon::render(&data.icon_bytes, recolor(data.icon_color, theme)),
+            data,
+            theme,
+            status: PanoTrayStatus::default(),
+            attention_icon: None,
+            overlay_icon: None,
+            now_playing_tooltip: None,
+        };
+        state.set_status(status);
+        state
+    }
+
+    fn set_status(&mut self, status: PanoTrayStatus) {
+        // not recoloured, as they are usually coloured
+        self.attention_icon = tray_icon::render(&status.attention_icon_bytes, Recolor::None);
+        self.overlay_icon = tray_icon::render(&status.overlay_icon_bytes, Recolor::None);
+        self.status = status;
+    }
+
+    fn set_data(&mut self, data: PanoTrayData) {
+        // an invalid icon keeps the previous one
+        if let Some(icon) =
+            tray_icon::render(&data.icon_bytes, recolor(data.icon_color, self.theme))
+        {
+            self.icon = Some(icon);
+        }
+        self.data = data;
+    }
+
+    fn set_theme(&mut self, theme: TrayTheme) {
+        self.theme = theme;
+
+        if matches!(
+            self.data.icon_color,
+            TrayIconColor::Auto | TrayIconColor::Accent
+        ) && let Some(icon) =
+            tray_icon::render(&self.data.icon_bytes, recolor(self.data.icon_color, theme))
+        {
+            self.icon = Some(icon);
+        }
+    }
+}
+
+fn recolor(icon_color: TrayIconColor, theme: TrayTheme) -> Recolor {
+    let theme_color = if theme.dark {
+        DARK_THEME_ICON_COLOR
     } else {
-        log::error!("Event loop not running");
+        LIGHT_THEME_ICON_COLOR
+    };
+
+    match icon_color {
+        TrayIconColor::Original => Recolor::None,
+        TrayIconColor::Invert => Recolor::Invert,
+        TrayIconColor::Auto => Recolor::Fill(theme_color),
+        TrayIconColor::Accent => Recolor::Fill(theme.accent.unwrap_or(theme_color)),
     }
 }
 
-fn compute_icon(png_bytes: &[u8], invert: bool) -> (Option<Icon>, u64) {
-    let mut hasher = DefaultHasher::new();
-    png_bytes.hash(&mut hasher);
-    invert.hash(&mut hasher);
-    let icon_hash = hasher.finish();
+fn pixmaps(icon: &Option<Arc<Vec<Icon>>>) -> Vec<Icon> {
+    icon.as_ref()
+        .map(|icon| icon.as_ref().clone())
+        .unwrap_or_default()
+}
+
+// MENU_ON_ACTIVATE is a const in ksni, so there is one tray type for each value of it
+struct PanoTray<const MENU_ON_ACTIVATE: bool> {
+    state: TrayState,
+}
+
+enum TrayHandle {
+    MenuOnActivate(ksni::Handle<PanoTray<true>>),
+    Activate(ksni::Handle<PanoTray<false>>),
+}
+
+impl TrayHandle {
+    async fn spawn(state: TrayState) -> Result<Self, ksni::Error> {
+        let sandboxed = ashpd::is_sandboxed();
 
-    let img = image::load_from_memory_with_format(png_bytes, image::ImageFormat::Png);
-    if let Ok(mut img) = img {
-        if invert {
-            img.invert();
+        if state.data.menu_on_activate {
+            PanoTray::<true> { state }
+                .disable_dbus_name(sandboxed)
+                // keeps running without a watcher, see watcher_offline
+                .assume_sni_available(true)
+                .spawn()
+                .await
+                .map(Self::MenuOnActivate)
+        } else {
+            PanoTray::<false> { state }
+                .disable_dbus_name(sandboxed)
+                // keeps running without a watcher, see watcher_offline
+                .assume_sni_available(true)
+                .spawn()
+                .await
+                .map(Self::Activate)
         }
-        let (width, height) = img.dimensions();
-        let mut data = img.into_rgba8().into_vec();
-        for pixel in data.chunks_exact_mut(4) {
-            pixel.rotate_right(1); // rgba to argb
+    }
+
+    fn menu_on_activate(&self) -> bool {
+        matches!(self, Self::MenuOnActivate(_))
+    }
+
+    async fn update<F: FnOnce(&mut TrayState)>(&self, f: F) {
+        match self {
+            Self::MenuOnActivate(handle) => handle.update(|tray| f(&mut tray.state)).await,
+            Self::Activate(handle) => handle.update(|tray| f(&mut tray.state)).await,
+        };
+    }
+
+    async fn shutdown(&self) {
+        match self {
+            Self::MenuOnActivate(handle) => handle.shutdown().await,
+            Self::Activate(handle) => handle.shutdown().await,
         }
-        (
-            Some(Icon {
-                width: width as i32,
-                height: height as i32,
-                data,
-            }),
-            icon_hash,
-        )
+    }
+}
+
+static TOKIO_USER_EVENT_SENDER: OnceLock<mpsc::Sender<TrayUpdate>> = OnceLock::new();
+// None until the first tray is spawned
+static AVAILABLE: Mutex<Option<bool>> = Mutex::new(None);
+// watcher_offline is called during spawn if there is no watcher yet
+static WATCHER_OFFLINE: AtomicBool = AtomicBool::new(false);
+static OUTGOING_TRAY_EVENT_TX: OnceLock<mpsc::Sender<JniCallback>> = OnceLock::new();
+// the theme may be known before the tray is created
+static THEME: Mutex<TrayTheme> = Mutex::new(TrayTheme {
+    dark: false,
+    accent: None,
+});
+
+pub fn update_tray(pano_tray_data: PanoTrayData) {
+    send_update(TrayUpdate::Data(pano_tray_data));
+}
+
+pub fn update_tray_status(pano_tray_status: PanoTrayStatus) {
+    send_update(TrayUpdate::Status(pano_tray_status));
+}
+
+pub fn update_tray_tooltip(now_playing_tooltip: Option<NowPlayingTooltip>) {
+    send_update(TrayUpdate::Tooltip(now_playing_tooltip));
+}
+
+/// Whether the tray is registered with a StatusNotifierWatcher, and so can be seen.
+pub fn is_available() -> bool {
+    AVAILABLE.lock().unwrap().unwrap_or(false)
+}
+
+fn set_available(available: bool) {
+    let mut prev = AVAILABLE.lock().unwrap();
+    if *prev != Some(available) {
+        *prev = Some(available);
+        send_callback(JniCallback::TrayAvailabilityChanged(available));
+    }
+}
+
+/// Called by the theme observer, so that the tray can recolour its icon.
+pub fn set_theme(theme: TrayTheme) {
+    *THEME.lock().unwrap() = theme;
+
+    if let Some(sender) = TOKIO_USER_EVENT_SENDER.get() {
+        let _ = sender.try_send(TrayUpdate::Theme(theme));
+    }
+}
+
+fn send_update(update: TrayUpdate) {
+    if let Some(sender) = TOKIO_USER_EVENT_SENDER.get() {
+        sender.try_send(update).unwrap_or_else(|_| {
+            log::error!("Failed to send tray event");
+        });
     } else {
-        log::error!("invalid png");
-        (None, 0)
+        log::error!("Event loop not running");
     }
 }
-impl ksni::Tray for PanoTray {
+
+impl<const MENU_ON_ACTIVATE: bool> ksni::Tray for PanoTray<MENU_ON_ACTIVATE> {
     fn id(&self) -> String {
         "com.arn.scrobble.tray".into()
     }
 
     fn icon_pixmap(&self) -> Vec<Icon> {
-        match &self.prev_icon {
-            Some(icon) => vec![icon.clone()],
-            None => vec![],
-        }
+        pixmaps(&self.state.icon)
+    }
+
+    fn icon_name(&self) -> String {
+        self.state.data.icon_name.clone()
+    }
+
+    fn status(&self) -> Status {
+        self.state.status.status
+    }
+
+    fn attention_icon_name(&self) -> String {
+        self.state.status.attention_icon_name.clone()
+    }
+
+    fn attention_icon_pixmap(&self) -> Vec<Icon> {
+        pixmaps(&self.state.attention_icon)
+    }
+
+    fn overlay_icon_name(&self) -> String {
+        self.state.status.overlay_icon_name.clone()
     }
 
-    const MENU_ON_ACTIVATE: bool = true;
+    fn overlay_icon_pixmap(&self) -> Vec<Icon> {
+        pixmaps(&self.state.overlay_icon)
+    }
+
+    const MENU_ON_ACTIVATE: bool = MENU_ON_ACTIVATE;
+
+    fn activate(&mut self, x: i32, y: i32) {
+        send_callback(JniCallback::TrayActivated(TrayActivation::Activate {
+            x,
+            y,
+        }));
+    }
+
+    // usually a middle click
+    fn secondary_activate(&mut self, x: i32, y: i32) {
+        send_callback(JniCallback::TrayActivated(
+            TrayActivation::SecondaryActivate { x, y },
+        ));
+    }
+
+    fn watcher_online(&self) {
+        WATCHER_OFFLINE.store(false, Ordering::Relaxed);
+        set_available(true);
+    }
+
+    // ksni registers the tray again when a watcher appears, so it is kept running
+    fn watcher_offline(&self, reason: OfflineReason) -> bool {
+        log::warn!("StatusNotifierWatcher is offline: {reason:?}");
+        WATCHER_OFFLINE.store(true, Ordering::Relaxed);
+        set_available(false);
+        true
+    }
+
+    fn scroll(&mut self, delta: i32, orientation: Orientation) {
+        send_callback(JniCallback::TrayActivated(TrayActivation::Scroll {
+            delta,
+            orientation,
+        }));
+    }
 
     fn title(&self) -> String {
         "Pano Scrobbler".to_string()
@@ -85,85 +420,258 @@ impl ksni::Tray for PanoTray {
         // if the tooltip is only one line, the description will be empty
         // also handle the case where the tooltip is empty
 
-        let mut lines = self.data.tooltip.trim().lines();
+        let mut lines = self.state.data.tooltip.trim().lines();
         let title = lines.next().unwrap_or("").to_string();
         let description = lines.collect::<Vec<&str>>().join("\n");
 
+        let Some(now_playing_tooltip) = &self.state.now_playing_tooltip else {
+            return ksni::ToolTip {
+                title,
+                description,
+                ..Default::default()
+            };
+        };
+
+        // the description has markup now, so the plain lines are escaped
+        let mut rich_description = now_playing_tooltip.description.clone();
+        if !description.is_empty() {
+            rich_description.push_str("<br/>");
+            rich_description
+                .push_str(&tray_tooltip::escape_markup(&description).replace('\n', "<br/>"));
+        }
+
         ksni::ToolTip {
             title,
-            description,
+            description: rich_description,
+            icon_pixmap: now_playing_tooltip.album_art.clone(),
             ..Default::default()
         }
     }
 
     fn menu(&self) -> Vec<MenuItem<Self>> {
-        self.data
-            .menu_items
-            .iter()
-            .map(|(id, text)| {
-                let id_owned = id.clone();
-                let text_owned = text.replace("_", "__"); // see ksni docs for why
-                match id.as_str() {
-                    "Separator" => MenuItem::Separator,
-                    _ => MenuItem::Standard(StandardItem {
-                        label: text_owned,
-                        activate: Box::new(move |_tray| {
-                            OUTGOING_TRAY_EVENT_TX
-                                .get()
-                                .unwrap()
-                                .try_send(JniCallback::TrayItemClicked(id_owned.clone()))
-                                .unwrap();
-                        }),
-                        enabled: !id.ends_with("Disabled"),
-                        ..Default::default()
-                    }),
+        build_menu(&self.state.data.menu)
+    }
+}
+
+// see ksni docs for why
+fn escape_label(label: &str) -> String {
+    label.replace("_", "__")
+}
+
+fn send_callback(callback: JniCallback) {
+    OUTGOING_TRAY_EVENT_TX
+        .get()
+        .unwrap()
+        .try_send(callback)
+        .unwrap_or_else(|_| log::error!("Failed to send tray callback"));
+}
+
+fn send_click(id: String, value: String) {
+    send_callback(JniCallback::TrayItemClicked(id, value));
+}
+
+fn find_item_mut<'a>(items: &'a mut [TrayMenuItem], item_id: &str) -> Option<&'a mut TrayMenuItem> {
+    for item in items {
+        match item {
+            TrayMenuItem::Submenu { children, .. } => {
+                if let Some(found) = find_item_mut(children, item_id) {
+                    return Some(found);
                 }
-            })
-            .collect()
+            }
+            TrayMenuItem::Checkmark { id, .. } | TrayMenuItem::Radio { id, .. }
+                if id == item_id =>
+            {
+                return Some(item);
+            }
+            _ => {}
+        }
     }
+
+    None
+}
+
+fn build_menu<const M: bool>(items: &[TrayMenuItem]) -> Vec<MenuItem<PanoTray<M>>> {
+    items
+        .iter()
+        .map(|item| match item {
+            TrayMenuItem::Standard {
+                id,
+                label,
+                enabled,
+                icon_name,
+                shortcut,
+            } => {
+                let id = id.clone();
+                MenuItem::Standard(StandardItem {
+                    label: escape_label(label),
+                    enabled: *enabled,
+                    icon_name: icon_name.clone(),
+                    shortcut: shortcut.clone(),
+                    activate: Box::new(move |_tray| send_click(id.clone(), String::new())),
+                    ..Default::default()
+                })
+            }
+
+            TrayMenuItem::Separator => MenuItem::Separator,
+
+            TrayMenuItem::Checkmark {
+                id,
+                label,
+                checked,
+                enabled,
+                icon_name,
+                shortcut,
+            } => {
+                let id = id.clone();
+                MenuItem::Checkmark(CheckmarkItem {
+                    label: escape_label(label),
+                    checked: *checked,
+                    enabled: *enabled,
+                    icon_name: icon_name.clone(),
+                    shortcut: shortcut.clone(),
+                    activate: Box::new(move |tray: &mut PanoTray<M>| {
+                        // toggled here too, so that the menu is right until java sends a new one
+                        if let Some(TrayMenuItem::Checkmark { checked, .. }) =
+                            find_item_mut(&mut tray.state.data.menu, &id)
+                        {
+                            *checked = !*checked;
+                            send_click(id.clone(), checked.to_string());
+                        }
+                    }),
+                    ..Default::default()
+                })
+            }
+
+            TrayMenuItem::Radio {
+                id,
+                selected,
+                options,
+            } => {
+                let id = id.clone();
+                let option_ids = options
+                    .iter()
+                    .map(|option| option.id.clone())
+                    .collect::<Vec<_>>();
+
+                MenuItem::RadioGroup(RadioGroup {
+                    selected: options
+                        .iter()
+                        .position(|option| &option.id == selected)
+                        .unwrap_or(usize::MAX),
+                    select: Box::new(move |tray: &mut PanoTray<M>, index| {
+                        let Some(option_id) = option_ids.get(index) else {
+                            return;
+                        };
+
+                        if let Some(TrayMenuItem::Radio { selected, .. }) =
+                            find_item_mut(&mut tray.state.data.menu, &id)
+                            && selected != option_id
+                        {
+                            *selected = option_id.clone();
+                            send_click(id.clone(), option_id.clone());
+                        }
+                    }),
+                    options: options
+                        .iter()
+                        .map(|option| RadioItem {
+                            label: escape_label(&option.label),
+                            enabled: option.enabled,
+                            icon_name: option.icon_name.clone(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                })
+            }
+
+            TrayMenuItem::Submenu {
+                label,
+                enabled,
+                icon_name,
+                children,
+            } => MenuItem::SubMenu(SubMenu {
+                label: escape_label(label),
+                enabled: *enabled,
+                icon_name: icon_name.clone(),
+                submenu: build_menu(children),
+                ..Default::default()
+            }),
+        })
+        .collect()
 }
 
 pub async fn tray_listener(
     callback_sender: mpsc::Sender<JniCallback>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let (sender, mut receiver) = mpsc::channel::<PanoTrayData>(10);
+    let (sender, mut receiver) = mpsc::channel::<TrayUpdate>(10);
     TOKIO_USER_EVENT_SENDER.set(sender).unwrap();
 
     OUTGOING_TRAY_EVENT_TX.set(callback_sender).unwrap();
 
-    let tray_handle: OnceLock<ksni::Handle<PanoTray>> = OnceLock::new();
-    let mut tray_init_attempted = false;
-
-    while let Some(tray_data) = receiver.recv().await {
-        if !tray_init_attempted {
-            tray_init_attempted = true;
+    let mut tray_handle: Option<TrayHandle> = None;
+    // kept for when the tray is (re)created
+    let mut tray_status = PanoTrayStatus::default();
+    let mut now_playing_tooltip = None;
 
-            let (prev_icon, prev_icon_hash) = compute_icon(&tray_data.png_bytes, tray_data.invert);
-            let tray = PanoTray {
-                data: tray_data,
-                prev_icon_hash,
-                prev_icon,
-            };
-            match tray.disable_dbus_name(ashpd::is_sandboxed()).spawn().await {
-                Ok(handle) => {
-                    let _ = tray_handle.set(handle);
+    while let Some(update) = receiver.recv().await {
+        let tray_data = match update {
+            TrayUpdate::Data(tray_data) => tray_data,
+            TrayUpdate::Status(status) => {
+                if let Some(handle) = &tray_handle {
+                    let status = status.clone();
+                    handle.update(|state| state.set_status(status)).await;
                 }
-                Err(e) => {
-                    log::error!("Failed to spawn tray: {e}");
+                tray_status = status;
+                continue;
+            }
+            TrayUpdate::Theme(theme) => {
+                if let Some(handle) = &tray_handle {
+                    handle.update(|state| state.set_theme(theme)).await;
                 }
+                continue;
             }
-        } else if let Some(handle) = tray_handle.get() {
-            handle
-                .update(|existing_tray| {
-                    let (icon, icon_hash) = compute_icon(&tray_data.png_bytes, tray_data.invert);
-                    if icon_hash != existing_tray.prev_icon_hash && icon.is_some() {
-                        existing_tray.prev_icon = icon;
-                        existing_tray.prev_icon_hash = icon_hash;
-                    }
-                    existing_tray.data = tray_data;
-                })
-                .await;
+            TrayUpdate::Tooltip(tooltip) => {
+                if let Some(handle) = &tray_handle {
+                    let tooltip = tooltip.clone();
+                    handle
+                        .update(|state| state.now_playing_tooltip = tooltip)
+                        .await;
+                }
+                now_playing_tooltip = tooltip;
+                continue;
+            }
+        };
+
+        match &tray_handle {
+            Some(handle) if handle.menu_on_activate() == tray_data.menu_on_activate => {
+                handle.update(|state| state.set_data(tray_data)).await;
+                continue;
+            }
+            Some(handle) => {
+                // the tray has to be recreated with the other type
+                handle.shutdown().await;
+            }
+            // also retried after a failed spawn
+            None => {}
         }
+
+        WATCHER_OFFLINE.store(false, Ordering::Relaxed);
+
+        let mut state = TrayState::new(tray_data, tray_status.clone(), *THEME.lock().unwrap());
+        state.now_playing_tooltip = now_playing_tooltip.clone();
+
+        tray_handle = match TrayHandle::spawn(state).await {
+            Ok(handle) => {
+                if !WATCHER_OFFLINE.load(Ordering::Relaxed) {
+                    set_available(true);
+                }
+                Some(handle)
+            }
+            Err(e) => {
+                log::error!("Failed to spawn tray: {e}");
+                set_available(false);
+                None
+            }
+        };
     }
 
     Ok(())
@@ -0,0 +1,164 @@
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops::FilterType};
+use ksni::Icon;
+use resvg::{tiny_skia, usvg};
+use std::{
+    collections::HashMap,
+    error::Error,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+// hosts pick the pixmap closest to the panel size
+const SIZES: [u32; 6] = [16, 22, 24, 32, 48, 64];
+
+// tooltips show art larger than the panel icon
+const ALBUM_ART_SIZES: [u32; 2] = [64, 128];
+
+// the icon usually switches between a few states, so this is plenty
+const CACHE_CAPACITY: usize = 16;
+
+#[derive(Clone, Copy, Hash)]
+pub enum Recolor {
+    None,
+    Invert,
+    // replaces the colour of every pixel and keeps its alpha, for monochrome icons
+    Fill([u8; 3]),
+}
+
+static CACHE: LazyLock<Mutex<HashMap<u64, Arc<Vec<Icon>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Renders a PNG or SVG icon at the standard tray sizes. Returns None for empty or invalid bytes.
+pub fn render(bytes: &[u8], recolor: Recolor) -> Option<Arc<Vec<Icon>>> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    recolor.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    if let Some(icon) = CACHE.lock().unwrap().get(&hash) {
+        return Some(icon.clone());
+    }
+
+    let img = if is_svg(bytes) {
+        rasterize_svg(bytes)
+    } else {
+        image::load_from_memory_with_format(bytes, image::ImageFormat::Png).map_err(Box::from)
+    };
+
+    let icon = match img {
+        Ok(mut img) => {
+            apply_recolor(&mut img, recolor);
+            Arc::new(resize(&img, &SIZES))
+        }
+        Err(e) => {
+            log::error!("Invalid tray icon: {e}");
+            return None;
+        }
+    };
+
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(hash, icon.clone());
+
+    Some(icon)
+}
+
+fn is_svg(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(1024)];
+    String::from_utf8_lossy(head).contains("<svg")
+}
+
+// rendered at the largest size, the smaller ones are scaled down from it like a png
+fn rasterize_svg(bytes: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default())?;
+
+    let largest = SIZES[SIZES.len() - 1] as f32;
+    let size = tree.size();
+    let scale = largest / size.width().max(size.height());
+    let width = (size.width() * scale).round().max(1.0) as u32;
+    let height = (size.height() * scale).round().max(1.0) as u32;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or("Invalid svg size")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    // tiny-skia stores premultiplied alpha
+    let data = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    let rgba = RgbaImage::from_raw(width, height, data).ok_or("Invalid svg pixmap")?;
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+fn apply_recolor(img: &mut DynamicImage, recolor: Recolor) {
+    match recolor {
+        Recolor::None => {}
+        Recolor::Invert => img.invert(),
+        Recolor::Fill(rgb) => {
+            let mut rgba = std::mem::take(img).into_rgba8();
+            for pixel in rgba.pixels_mut() {
+                pixel.0[..3].copy_from_slice(&rgb);
+            }
+            *img = DynamicImage::ImageRgba8(rgba);
+        }
+    }
+}
+
+fn to_icon(img: &DynamicImage) -> Icon {
+    let (width, height) = img.dimensions();
+    let mut data = img.to_rgba8().into_vec();
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.rotate_right(1); // rgba to argb
+    }
+
+    Icon {
+        width: width as i32,
+        height: height as i32,
+        data,
+    }
+}
+
+fn resize(img: &DynamicImage, sizes: &[u32]) -> Vec<Icon> {
+    let (width, height) = img.dimensions();
+    let largest = width.max(height);
+
+    // upscaling only adds blur, so larger sizes are left to the host
+    let mut pixmaps = sizes
+        .iter()
+        .filter(|size| **size <= largest)
+        .map(|size| to_icon(&img.resize(*size, *size, FilterType::Lanczos3)))
+        .collect::<Vec<_>>();
+
+    if sizes.last().is_some_and(|max| largest < *max) && !sizes.contains(&largest) {
+        pixmaps.push(to_icon(img));
+    }
+
+    pixmaps
+}
+
+/// Decodes album art of any supported format for the tooltip. Not cached here,
+/// as it changes with every track.
+pub fn render_album_art(bytes: &[u8]) -> Option<Vec<Icon>> {
+    match image::load_from_memory(bytes) {
+        Ok(img) => Some(resize(&img, &ALBUM_ART_SIZES)),
+        Err(e) => {
+            log::debug!("Failed to decode album art: {e}");
+            None
+        }
+    }
+}
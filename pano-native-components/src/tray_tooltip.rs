@@ -0,0 +1,227 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use ksni::Icon;
+use serde::Deserialize;
+use tokio::sync::{broadcast, watch};
+
+use crate::{
+    media_events::PlaybackState,
+    now_playing::{self, NowPlaying, NowPlayingEvent},
+    tray, tray_icon,
+};
+
+// deserialized from the json passed to setTrayTooltipLinux
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrayTooltipConfig {
+    // only local art files are shown, see now_playing::album_art
+    pub album_art: bool,
+    // a line like "1:23 / 4:56", updated every second while playing
+    pub progress: bool,
+}
+
+impl Default for TrayTooltipConfig {
+    fn default() -> Self {
+        Self {
+            album_art: true,
+            progress: true,
+        }
+    }
+}
+
+/// The now playing part of the tooltip, shown above the lines from setTrayLinux.
+#[derive(Clone)]
+pub struct NowPlayingTooltip {
+    pub description: String,
+    pub album_art: Vec<Icon>,
+}
+
+static CONFIG_TX: OnceLock<watch::Sender<Option<TrayTooltipConfig>>> = OnceLock::new();
+
+/// Pass None to go back to the plain tooltip from setTrayLinux.
+pub fn set_config(config: Option<TrayTooltipConfig>) {
+    let tx = CONFIG_TX.get_or_init(|| {
+        let (tx, rx) = watch::channel(None);
+
+        std::thread::spawn(move || {
+            if let Err(e) = updater(rx) {
+                log::error!("Tray tooltip updater stopped: {e}");
+            }
+        });
+
+        tx
+    });
+
+    tx.send_replace(config);
+}
+
+// the tooltip description is a subset of html
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_time(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[derive(Default)]
+struct Tracker {
+    // app id, position in ms and when it was reported
+    position: Option<(String, i64, Instant)>,
+    // url of the rendered art
+    album_art: Option<(String, Vec<Icon>)>,
+    playing: bool,
+}
+
+impl Tracker {
+    fn on_event(&mut self, event: &NowPlayingEvent) {
+        if let NowPlayingEvent::Playback { app_id, playback } = event {
+            self.position = Some((app_id.clone(), playback.position, Instant::now()));
+        }
+    }
+
+    fn position(&mut self, app_id: &str, reported: i64, playing: bool) -> i64 {
+        // no playback event since the updater started, or another app is playing now
+        if !matches!(&self.position, Some((id, _, _)) if id == app_id) {
+            self.position = Some((app_id.to_string(), reported, Instant::now()));
+        }
+
+        let (_, position, at) = self.position.as_ref().unwrap();
+
+        if playing && *position >= 0 {
+            position + at.elapsed().as_millis() as i64
+        } else {
+            *position
+        }
+    }
+
+    fn album_art(&mut self) -> Vec<Icon> {
+        let Some(album_art) = now_playing::album_art() else {
+            self.album_art = None;
+            return vec![];
+        };
+
+        match &self.album_art {
+            Some((url, icons)) if *url == album_art.url => icons.clone(),
+            _ => {
+                let icons = tray_icon::render_album_art(&album_art.bytes).unwrap_or_default();
+                self.album_art = Some((album_art.url.clone(), icons.clone()));
+                icons
+            }
+        }
+    }
+
+    fn build(&mut self, config: &TrayTooltipConfig) -> Option<NowPlayingTooltip> {
+        self.playing = false;
+
+        let NowPlaying { app_id, session } = now_playing::current()?;
+        let metadata = session.metadata?;
+        let playback = session.playback?;
+
+        if matches!(playback.state, PlaybackState::Stopped | PlaybackState::None) {
+            return None;
+        }
+
+        self.playing = playback.state == PlaybackState::Playing;
+
+        let mut lines = vec![format!("<b>{}</b>", escape_markup(&metadata.title))];
+
+        if !metadata.artist.is_empty() {
+            lines.push(escape_markup(&metadata.artist));
+        }
+
+        if !metadata.album.is_empty() {
+            lines.push(format!("<i>{}</i>", escape_markup(&metadata.album)));
+        }
+
+        if config.progress {
+            let position = self.position(&app_id, playback.position, self.playing);
+
+            let mut progress = if position < 0 {
+                String::new()
+            } else if metadata.duration > 0 {
+                format!(
+                    "{} / {}",
+                    format_time(position.min(metadata.duration)),
+                    format_time(metadata.duration)
+                )
+            } else {
+                format_time(position)
+            };
+
+            if playback.state == PlaybackState::Paused {
+                progress = format!("⏸ {progress}");
+            }
+
+            if !progress.trim().is_empty() {
+                lines.push(progress.trim().to_string());
+            }
+        }
+
+        let album_art = if config.album_art {
+            self.album_art()
+        } else {
+            vec![]
+        };
+
+        Some(NowPlayingTooltip {
+            description: lines.join("<br/>"),
+            album_art,
+        })
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn updater(
+    mut config_rx: watch::Receiver<Option<TrayTooltipConfig>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tracker = Tracker::default();
+    let mut events = now_playing::subscribe();
+    // description and art url of the last tooltip sent to the tray
+    let mut last_sent = None;
+    let mut force_send = true;
+
+    loop {
+        let config = config_rx.borrow_and_update().clone();
+        let tooltip = config.as_ref().and_then(|config| tracker.build(config));
+
+        let sent = tooltip.as_ref().map(|tooltip| {
+            let art_url = tracker.album_art.as_ref().map(|(url, _)| url.clone());
+            (tooltip.description.clone(), art_url)
+        });
+        if force_send || sent != last_sent {
+            force_send = false;
+            last_sent = sent;
+            tray::update_tray_tooltip(tooltip);
+        }
+
+        let ticking = tracker.playing && config.is_some_and(|config| config.progress);
+
+        tokio::select! {
+            changed = config_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                // a new config may add or remove the art
+                force_send = true;
+            }
+
+            event = events.recv() => match event {
+                Ok(event) => tracker.on_event(&event),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+
+            _ = tokio::time::sleep(Duration::from_secs(1)), if ticking => {}
+        }
+    }
+
+    Ok(())
+}